@@ -0,0 +1,49 @@
+//! The signed message on Gnosis block 33073608 the crates' tests share, read
+//! from `fixtures/gnosis-33073608-witness.json`. That witness was built from
+//! the `eth_getBlockByNumber` and `eth_getProof` responses in
+//! `lib/fetch/fixtures`, whose tests check that both still agree.
+
+use crate::{Inputs, Sp1SafeParams};
+
+pub const CHAIN_ID: u64 = 100;
+pub const BLOCK_NUMBER: u64 = 33073608;
+pub const SAFE_ADDRESS: &str = "0x38ba7f4278a1482fa0a7bc8b261a9a673336eddc";
+pub const MESSAGE_HASH: &str = "0xa225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7";
+
+pub const WITNESS: &str = include_str!("../fixtures/gnosis-33073608-witness.json");
+
+pub fn inputs() -> Inputs {
+    let mut json = serde_json::from_str::<serde_json::Value>(WITNESS).expect("fixture is json");
+    serde_json::from_value(json["inputs"].take()).expect("fixture holds inputs")
+}
+
+#[cfg(feature = "witness")]
+pub fn witness() -> crate::witness::Witness {
+    crate::witness::Witness::from_bytes(WITNESS.as_bytes()).expect("fixture is a witness")
+}
+
+pub fn params() -> Sp1SafeParams {
+    Sp1SafeParams {
+        chain_id: CHAIN_ID,
+        safe_address: SAFE_ADDRESS.to_string(),
+        message_hash: MESSAGE_HASH.to_string(),
+        callback_url: None,
+    }
+}
+
+/// What proving the fixture yields, with a dummy `0x00` proof.
+#[cfg(feature = "challenge")]
+pub fn result() -> crate::Sp1SafeResult {
+    let inputs = inputs();
+    let block_hash = crate::keccak256(&inputs.header_rlp);
+    let challenge = crate::compute_challenge(inputs.safe_address, inputs.msg_hash);
+    crate::Sp1SafeResult {
+        chain_id: CHAIN_ID,
+        safe_address: SAFE_ADDRESS.to_string(),
+        message_hash: MESSAGE_HASH.to_string(),
+        block_number: BLOCK_NUMBER,
+        block_hash: format!("0x{}", const_hex::encode(block_hash)),
+        challenge: format!("0x{}", const_hex::encode(challenge)),
+        proof: "0x00".to_string(),
+    }
+}
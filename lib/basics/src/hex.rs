@@ -0,0 +1,184 @@
+//! Serde adapters writing bytes as `0x`-prefixed hex strings to human-readable
+//! formats like JSON while leaving binary formats untouched, so bincode and
+//! thus the zkVM's encoding of `Inputs` stays compact and unchanged. Reading
+//! JSON also accepts the plain integer arrays written without these adapters.
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a hex string or an array of bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+        const_hex::decode(v).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            out.push(byte);
+        }
+        Ok(out)
+    }
+}
+
+/// Bytes deserialized from either hex or an integer array.
+struct HexBytes(Vec<u8>);
+
+impl<'de> Deserialize<'de> for HexBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BytesVisitor).map(HexBytes)
+    }
+}
+
+/// Bytes serialized as hex.
+struct Hex<'a>(&'a [u8]);
+
+impl Serialize for Hex<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", const_hex::encode(self.0)))
+    }
+}
+
+/// For `Vec<u8>` fields.
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            Hex(v).serialize(serializer)
+        } else {
+            v.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            HexBytes::deserialize(deserializer).map(|b| b.0)
+        } else {
+            Vec::<u8>::deserialize(deserializer)
+        }
+    }
+}
+
+/// For `[u8; N]` fields.
+pub mod array {
+    use super::*;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        v: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        [u8; N]: Serialize,
+    {
+        if serializer.is_human_readable() {
+            Hex(v).serialize(serializer)
+        } else {
+            v.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error>
+    where
+        [u8; N]: Deserialize<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let bytes = HexBytes::deserialize(deserializer)?.0;
+            let len = bytes.len();
+            bytes
+                .try_into()
+                .map_err(|_| de::Error::invalid_length(len, &format!("{} bytes", N).as_str()))
+        } else {
+            <[u8; N]>::deserialize(deserializer)
+        }
+    }
+}
+
+/// For `Vec<Vec<u8>>` fields such as trie proofs.
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &Vec<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_seq(v.iter().map(|b| Hex(b)))
+        } else {
+            v.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        if deserializer.is_human_readable() {
+            let nodes = Vec::<HexBytes>::deserialize(deserializer)?;
+            Ok(nodes.into_iter().map(|b| b.0).collect())
+        } else {
+            Vec::<Vec<u8>>::deserialize(deserializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::{self, inputs};
+    use crate::Inputs;
+
+    #[test]
+    fn test_json_hex() {
+        let json = serde_json::to_value(inputs()).unwrap();
+        assert_eq!(json["safe_address"], fixtures::SAFE_ADDRESS);
+        assert_eq!(json["msg_hash"], fixtures::MESSAGE_HASH);
+        assert!(json["account_proof"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("0xf9"));
+        assert!(json["header_rlp"].as_str().unwrap().starts_with("0xf9"));
+        let read: Inputs = serde_json::from_value(json).unwrap();
+        assert_eq!(read.storage_proof, inputs().storage_proof);
+        assert_eq!(read.header_rlp, inputs().header_rlp);
+    }
+
+    #[test]
+    fn test_json_integer_arrays() {
+        let mut json = serde_json::to_value(inputs()).unwrap();
+        json["msg_hash"] = serde_json::json!(vec![0xa2; 32]);
+        json["header_rlp"] = serde_json::json!([0xf9, 0x02, 0x5a]);
+        let read: Inputs = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(read.msg_hash, [0xa2; 32]);
+
+        json["safe_address"] = serde_json::json!("0x3838");
+        assert!(serde_json::from_value::<Inputs>(json).is_err());
+    }
+
+    #[test]
+    fn test_bincode_unchanged() {
+        let i = inputs();
+        let plain = (
+            i.safe_address,
+            i.msg_hash,
+            i.state_root,
+            i.storage_root,
+            i.state_trie_key,
+            i.storage_trie_key,
+            &i.account_proof,
+            &i.storage_proof,
+            &i.header_rlp,
+        );
+        assert_eq!(
+            bincode::serialize(&i).unwrap(),
+            bincode::serialize(&plain).unwrap()
+        );
+    }
+}
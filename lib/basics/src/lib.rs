@@ -1,41 +1,138 @@
+#[cfg(feature = "challenge")]
+use ark_bn254::Fr;
+#[cfg(feature = "challenge")]
+use ark_ff::{BigInteger, PrimeField};
+#[cfg(feature = "challenge")]
+use light_poseidon::{Poseidon, PoseidonHasher};
 use serde::{Deserialize, Serialize};
 use tiny_keccak::{Hasher, Keccak};
 
+#[cfg(any(feature = "fixtures", all(test, feature = "serde-hex")))]
+pub mod fixtures;
+#[cfg(feature = "serde-hex")]
+pub mod hex;
+pub mod public_values;
+#[cfg(feature = "sealed")]
+pub mod sealed;
+#[cfg(feature = "witness")]
+pub mod witness;
+
+pub use public_values::PublicValues;
+
 pub const SAFE_SIGNED_MESSAGES_SLOT: [u8; 32] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7,
 ];
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Inputs {
-    pub safe_address: [u8; 20],      // Safe address
-    pub msg_hash: [u8; 32],          // Custom msg hash
-    pub state_root: [u8; 32],        // eth_getBlockBy*::response.stateRoot
-    pub storage_root: [u8; 32],      // eth_getProof::response.storageHash
-    pub state_trie_key: [u8; 32],    // keccak256(safe)
-    pub storage_trie_key: [u8; 32],  // keccak256(msg_hash + uint256(7))
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::array"))]
+    pub safe_address: [u8; 20], // Safe address
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::array"))]
+    pub msg_hash: [u8; 32], // Custom msg hash
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::array"))]
+    pub state_root: [u8; 32], // eth_getBlockBy*::response.stateRoot
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::array"))]
+    pub storage_root: [u8; 32], // eth_getProof::response.storageHash
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::array"))]
+    pub state_trie_key: [u8; 32], // keccak256(safe)
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::array"))]
+    pub storage_trie_key: [u8; 32], // keccak256(msg_hash + uint256(7))
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::vec"))]
     pub account_proof: Vec<Vec<u8>>, // eth_getProof::response.accountProof
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::vec"))]
     pub storage_proof: Vec<Vec<u8>>, // eth_getProof::response.storageProof.proof
-    pub header_rlp: Vec<u8>,         // RLP-encoded header
+    #[cfg_attr(feature = "serde-hex", serde(with = "crate::hex::bytes"))]
+    pub header_rlp: Vec<u8>, // RLP-encoded header
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Sp1SafeParams {
+    #[cfg_attr(feature = "openapi", schema(example = 100))]
     pub chain_id: u64,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0x38Ba7f4278A1482FA0a7bC8B261a9A673336EDDc")
+    )]
     pub safe_address: String,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0xa225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7")
+    )]
     pub message_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(example = "https://example.org/hook"))]
+    pub callback_url: Option<String>, // POSTed the Sp1SafeResult once ready
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChallengeParams {
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0x38Ba7f4278A1482FA0a7bC8B261a9A673336EDDc")
+    )]
+    pub safe_address: String,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0xa225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7")
+    )]
+    pub message_hash: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Sp1SafeResult {
+    #[cfg_attr(feature = "openapi", schema(example = 100))]
     pub chain_id: u64,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0x38Ba7f4278A1482FA0a7bC8B261a9A673336EDDc")
+    )]
     pub safe_address: String,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0xa225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7")
+    )]
     pub message_hash: String,
+    #[cfg_attr(feature = "openapi", schema(example = 33119703))]
     pub block_number: u64,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0x0f5d9b4f3b6fae6a2d3c3cdbb6e6ef5bd2ac9bdfc4ef55c4b1a1ff0f3cf1ba3e")
+    )]
     pub block_hash: String,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(example = "0x1c3a5c7e0ea7a1f2f1d2b4ffbbd0a3c4e1a8b6c1e9f8e7d6c5b4a39281706f5e")
+    )]
     pub challenge: String,
+    /// Hex-encoded bincode proof, just `0x` in `execute` prover mode
+    #[cfg_attr(feature = "openapi", schema(example = "0x..."))]
     pub proof: String,
 }
 
+/// Proof job progress as streamed by the server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Progress {
+    Queued,
+    InputsFetched { block_number: u64 },
+    Executed { cycles: u64 },
+    CoreProved,
+    Compressed,
+    PlonkWrapped,
+    Done { result: Sp1SafeResult },
+    Failed { error: String },
+}
+
+impl Progress {
+    pub fn is_final(&self) -> bool {
+        matches!(self, Progress::Done { .. } | Progress::Failed { .. })
+    }
+}
+
 pub fn concat_bytes64(a: [u8; 32], b: [u8; 32]) -> [u8; 64] {
     // https://stackoverflow.com/a/76573243
     unsafe { core::mem::transmute::<[[u8; 32]; 2], [u8; 64]>([a, b]) }
@@ -52,3 +149,60 @@ pub fn keccak256<T: AsRef<[u8]>>(input: T) -> [u8; 32] {
     k.finalize(&mut out);
     out
 }
+
+/// Poseidon hash of the left-padded Safe address and message hash, linking a
+/// proof to a Safe and message without revealing either.
+#[cfg(feature = "challenge")]
+pub fn compute_challenge(safe_address: [u8; 20], msg_hash: [u8; 32]) -> [u8; 32] {
+    let mut poseidon = Poseidon::<Fr>::new_circom(2).expect("poseidon init failed");
+    // _mod_order might reduce fr2 i.e. it has 2 msg_hash preimages aka collision;
+    // since the 20-byte Safe address cannot exceed bn254's scalar field _mod_order
+    // is always a noop for fr1, i.e. it has strictly 1 Safe address preimage:
+    // no collisions; consequently "cross-account" collisions can never occur
+    let fr1 = Fr::from_be_bytes_mod_order(&lpad_bytes32(safe_address));
+    let fr2 = Fr::from_be_bytes_mod_order(&msg_hash);
+    poseidon
+        .hash(&[fr1, fr2])
+        .expect("poseidon hash failed")
+        .into_bigint()
+        .to_bytes_be()
+        .try_into()
+        .expect("converting field elements to bytes failed")
+}
+
+#[cfg(all(test, feature = "challenge"))]
+mod test {
+    use super::*;
+
+    fn unhex<const N: usize>(s: &str) -> [u8; N] {
+        core::array::from_fn(|i| u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap())
+    }
+
+    #[test]
+    fn test_compute_challenge() {
+        // (safe_address, msg_hash, challenge)
+        let vectors = [
+            (
+                "0000000000000000000000000000000000000000",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "2098f5fb9e239eab3ceac3f27b81e481dc3124d55ffed523a839ee8446b64864",
+            ),
+            (
+                "38ba7f4278a1482fa0a7bc8b261a9a673336eddc",
+                "a225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7",
+                "110bf0d0ea1251f73ef2ce30e6aec8fb2423e7c5d6563104789100168e252811",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffff",
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "19f4faaa25ddc8475ea602772ef9248ca31d181fe35716327f29300f239c7861",
+            ),
+        ];
+        for (safe_address, msg_hash, challenge) in vectors {
+            assert_eq!(
+                compute_challenge(unhex(safe_address), unhex(msg_hash)),
+                unhex::<32>(challenge)
+            );
+        }
+    }
+}
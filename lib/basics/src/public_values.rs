@@ -0,0 +1,188 @@
+//! The program's public values, committed by the guest as the ABI encoding of
+//! `(uint8 version, bytes32 blockHash, bytes32 challenge)` so hosts decode
+//! them by schema rather than read order, and Solidity consumers can just
+//! `abi.decode(publicValues, (uint8, bytes32, bytes32))` them.
+
+use core::fmt;
+
+pub const VERSION: u8 = 1;
+/// Length of the ABI encoding, three 32-byte words.
+pub const LEN: usize = 96;
+/// Solidity tuple type the encoding decodes to.
+pub const SOLIDITY_TYPE: &str = "(uint8 version, bytes32 blockHash, bytes32 challenge)";
+
+#[derive(Debug)]
+pub enum PublicValuesError {
+    UnsupportedVersion(u8),
+    Malformed(&'static str),
+    ChallengeMismatch,
+}
+
+impl fmt::Display for PublicValuesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicValuesError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported public values version {}, expected {}",
+                v, VERSION
+            ),
+            PublicValuesError::Malformed(e) => write!(f, "malformed public values: {}", e),
+            PublicValuesError::ChallengeMismatch => {
+                write!(f, "challenge doesn't match the Safe and message")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PublicValuesError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicValues {
+    pub version: u8,
+    /// Hash of the header whose state root the storage proof is anchored to
+    pub block_hash: [u8; 32],
+    /// Poseidon hash of the Safe address and message hash
+    pub challenge: [u8; 32],
+}
+
+impl PublicValues {
+    pub fn new(block_hash: [u8; 32], challenge: [u8; 32]) -> Self {
+        PublicValues {
+            version: VERSION,
+            block_hash,
+            challenge,
+        }
+    }
+
+    /// ABI encoding, as committed by the guest.
+    pub fn encode(&self) -> [u8; LEN] {
+        let mut out = [0u8; LEN];
+        out[31] = self.version;
+        out[32..64].copy_from_slice(&self.block_hash);
+        out[64..].copy_from_slice(&self.challenge);
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, PublicValuesError> {
+        if bytes.len() != LEN {
+            return Err(PublicValuesError::Malformed("expected 96 bytes"));
+        }
+        if bytes[..31].iter().any(|b| *b != 0) {
+            return Err(PublicValuesError::Malformed("version exceeds uint8"));
+        }
+        if bytes[31] != VERSION {
+            return Err(PublicValuesError::UnsupportedVersion(bytes[31]));
+        }
+        Ok(PublicValues {
+            version: bytes[31],
+            block_hash: bytes[32..64].try_into().expect("32 bytes"),
+            challenge: bytes[64..].try_into().expect("32 bytes"),
+        })
+    }
+
+    /// Decodes hex-encoded block hash and challenge as found in results and
+    /// re-encodes them to the bytes their proof commits to.
+    pub fn from_hex(block_hash: &str, challenge: &str) -> Result<Self, PublicValuesError> {
+        Ok(PublicValues::new(unhex(block_hash)?, unhex(challenge)?))
+    }
+}
+
+fn unhex<const N: usize>(s: &str) -> Result<[u8; N], PublicValuesError> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    // slicing by byte offset below would panic inside multibyte chars
+    if !s.is_ascii() {
+        return Err(PublicValuesError::Malformed("invalid hex"));
+    }
+    if s.len() != 2 * N {
+        return Err(PublicValuesError::Malformed("unexpected hex length"));
+    }
+    let mut out = [0u8; N];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)
+            .map_err(|_| PublicValuesError::Malformed("invalid hex"))?;
+    }
+    Ok(out)
+}
+
+#[cfg(feature = "challenge")]
+impl crate::Sp1SafeResult {
+    /// The public values a proof of this result must commit to, once its
+    /// challenge is checked to be the one of its Safe address and message
+    /// hash. Verifying against the claimed challenge alone would accept a
+    /// valid proof for any other Safe and message relabeled as this one.
+    pub fn checked_public_values(&self) -> Result<PublicValues, PublicValuesError> {
+        let public_values = PublicValues::from_hex(&self.block_hash, &self.challenge)?;
+        let challenge =
+            crate::compute_challenge(unhex(&self.safe_address)?, unhex(&self.message_hash)?);
+        if challenge != public_values.challenge {
+            return Err(PublicValuesError::ChallengeMismatch);
+        }
+        Ok(public_values)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let values = PublicValues::new([0xb3; 32], [0xc4; 32]);
+        let bytes = values.encode();
+        assert!(bytes[..31].iter().all(|b| *b == 0));
+        assert_eq!(bytes[31], VERSION);
+        assert_eq!(PublicValues::decode(&bytes).unwrap(), values);
+
+        let hex = |b: [u8; 32]| b.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        let from_hex =
+            PublicValues::from_hex(&format!("0x{}", hex([0xb3; 32])), &hex([0xc4; 32])).unwrap();
+        assert_eq!(from_hex, values);
+    }
+
+    #[test]
+    fn test_rejects() {
+        // unversioned layout of earlier releases
+        assert!(matches!(
+            PublicValues::decode(&[0xb3; 64]),
+            Err(PublicValuesError::Malformed(_))
+        ));
+        let mut bytes = PublicValues::new([0xb3; 32], [0xc4; 32]).encode();
+        bytes[31] = 2;
+        assert!(matches!(
+            PublicValues::decode(&bytes),
+            Err(PublicValuesError::UnsupportedVersion(2))
+        ));
+        bytes[0] = 1;
+        assert!(matches!(
+            PublicValues::decode(&bytes),
+            Err(PublicValuesError::Malformed(_))
+        ));
+        // 64 bytes, one of the 2-byte pairs splitting a multibyte char
+        let multibyte = format!("aé{}", "a".repeat(61));
+        assert_eq!(multibyte.len(), 64);
+        assert!(matches!(
+            PublicValues::from_hex(&multibyte, &"c4".repeat(32)),
+            Err(PublicValuesError::Malformed(_))
+        ));
+    }
+
+    #[cfg(all(feature = "challenge", feature = "serde-hex"))]
+    #[test]
+    fn test_checked_public_values() {
+        let result = crate::fixtures::result();
+        let public_values = result.checked_public_values().unwrap();
+        assert_eq!(
+            public_values,
+            PublicValues::from_hex(&result.block_hash, &result.challenge).unwrap()
+        );
+
+        let tampered = crate::Sp1SafeResult {
+            safe_address: "0x000000000000000000000000000000000000dead".to_string(),
+            ..result
+        };
+        assert!(matches!(
+            tampered.checked_public_values(),
+            Err(PublicValuesError::ChallengeMismatch)
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+//! Witnesses sealed to a server's X25519 public key, so the Safe address and
+//! message hash are opaque to anything between sender and server that lacks
+//! the server's secret key. That only holds if senders pin the server's key
+//! out of band: a key fetched from `GET /witness/key` over the same channel
+//! is only as trustworthy as that channel, and anything able to read the
+//! cleartext traffic could just as well swap in its own key. This is a custom
+//! ECIES-style scheme, not RFC 9180 HPKE, though close to HPKE's base mode
+//! with a single message per ephemeral key:
+//!
+//! - the sender generates a fresh X25519 key pair, `enc` its public key, and
+//!   computes the shared secret `dh = X25519(ephemeral secret, server public)`
+//! - `okm = HKDF-SHA256(salt = enc || server public, ikm = dh,
+//!   info = "sp1-safe sealed witness v1")`, 44 bytes
+//! - `key = okm[..32]`, `nonce = okm[32..]`, reusing the nonce is fine as
+//!   each key encrypts exactly one message
+//! - the sealed witness is `enc || ChaCha20-Poly1305(key, nonce, aad = "",
+//!   bincode(Inputs))`
+//!
+//! Senders are anonymous, the server can't tell who sealed a witness, only
+//! that it was sealed to its key. Low-order `enc` points yielding an all-zero
+//! shared secret are rejected.
+
+use crate::Inputs;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use core::fmt;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::EphemeralSecret;
+pub use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+pub const KEM: &str = "x25519-hkdf-sha256";
+pub const AEAD: &str = "chacha20poly1305";
+const INFO: &[u8] = b"sp1-safe sealed witness v1";
+
+#[derive(Debug)]
+pub enum SealError {
+    TooShort,
+    LowOrder,
+    Decryption,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::TooShort => write!(f, "sealed witness too short"),
+            SealError::LowOrder => write!(f, "sealed witness with low order key"),
+            SealError::Decryption => write!(f, "sealed witness decryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+fn cipher(shared: &[u8], enc: &PublicKey, recipient: &PublicKey) -> (ChaCha20Poly1305, Nonce) {
+    let salt = [enc.as_bytes().as_slice(), recipient.as_bytes().as_slice()].concat();
+    let mut okm = Zeroizing::new([0u8; 44]);
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(INFO, okm.as_mut_slice())
+        .expect("44 bytes is a valid hkdf-sha256 length");
+    (
+        ChaCha20Poly1305::new(Key::from_slice(&okm[..32])),
+        *Nonce::from_slice(&okm[32..]),
+    )
+}
+
+/// Seals a plaintext to given recipient public key under a fresh ephemeral
+/// key.
+pub fn seal(recipient: &PublicKey, plaintext: &[u8]) -> Vec<u8> {
+    let secret = EphemeralSecret::random();
+    let enc = PublicKey::from(&secret);
+    let shared = secret.diffie_hellman(recipient);
+    let (cipher, nonce) = cipher(shared.as_bytes(), &enc, recipient);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .expect("chacha20poly1305 encryption failed");
+    [enc.as_bytes().as_slice(), &ciphertext].concat()
+}
+
+/// Seals bincode-encoded inputs, the body `POST /witness/sealed` takes.
+pub fn seal_inputs(recipient: &PublicKey, inputs: &Inputs) -> Vec<u8> {
+    let plaintext =
+        Zeroizing::new(bincode::serialize(inputs).expect("inputs are bincode-serializable"));
+    seal(recipient, &plaintext)
+}
+
+pub struct SealingKey {
+    secret: StaticSecret,
+    pub public: PublicKey,
+}
+
+impl SealingKey {
+    pub fn new(secret: StaticSecret) -> Self {
+        let public = PublicKey::from(&secret);
+        SealingKey { secret, public }
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, SealError> {
+        if sealed.len() < 32 {
+            return Err(SealError::TooShort);
+        }
+        let enc = PublicKey::from(<[u8; 32]>::try_from(&sealed[..32]).expect("32 bytes"));
+        let shared = self.secret.diffie_hellman(&enc);
+        if !shared.was_contributory() {
+            return Err(SealError::LowOrder);
+        }
+        let (cipher, nonce) = cipher(shared.as_bytes(), &enc, &self.public);
+        cipher
+            .decrypt(&nonce, &sealed[32..])
+            .map(Zeroizing::new)
+            .map_err(|_| SealError::Decryption)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let key = SealingKey::new(StaticSecret::random());
+        let sealed = seal(&key.public, b"witness");
+        assert_eq!(key.open(&sealed).unwrap().as_slice(), b"witness");
+    }
+
+    #[test]
+    fn test_open_tampered() {
+        let key = SealingKey::new(StaticSecret::random());
+        let mut sealed = seal(&key.public, b"witness");
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(key.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_open_low_order() {
+        let key = SealingKey::new(StaticSecret::random());
+        // the identity point, any shared secret with it is all zeros
+        let sealed = [[0u8; 32].as_slice(), &[0u8; 64]].concat();
+        assert!(matches!(key.open(&sealed), Err(SealError::LowOrder)));
+    }
+
+    #[test]
+    fn test_open_wrong_key() {
+        let key = SealingKey::new(StaticSecret::random());
+        let other = SealingKey::new(StaticSecret::random());
+        let sealed = seal(&other.public, b"witness");
+        assert!(key.open(&sealed).is_err());
+    }
+}
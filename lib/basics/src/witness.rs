@@ -0,0 +1,177 @@
+//! Witness files carrying `Inputs` between a fetching and an air-gapped
+//! proving machine, either compact bincode or human-readable JSON with hex
+//! fields. Both start with a header naming the format version so witnesses
+//! from other releases get rejected instead of misread:
+//!
+//! - bincode: `b"sp1safew" || version as u16 LE || bincode((chain_id, block_number, inputs))`
+//! - JSON: `{ "format": "sp1-safe-witness", "version": 1, "chain_id": .., "block_number": .., "inputs": { .. } }`
+
+use crate::Inputs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const MAGIC: &[u8; 8] = b"sp1safew";
+pub const FORMAT: &str = "sp1-safe-witness";
+pub const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum WitnessError {
+    /// Neither starts with the bincode magic nor is a JSON witness
+    UnknownFormat,
+    UnsupportedVersion(u16),
+    Malformed(String),
+}
+
+impl fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessError::UnknownFormat => write!(f, "not a sp1-safe witness"),
+            WitnessError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported witness version {}, expected {}, refetch with this release",
+                v, VERSION
+            ),
+            WitnessError::Malformed(e) => write!(f, "malformed witness: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WitnessError {}
+
+#[derive(Clone, Debug)]
+pub struct Witness {
+    pub chain_id: u64,
+    pub block_number: u64,
+    pub inputs: Inputs,
+}
+
+#[derive(Deserialize)]
+struct JsonHeader {
+    format: Option<String>,
+    version: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonWitness {
+    format: String,
+    version: u16,
+    chain_id: u64,
+    block_number: u64,
+    inputs: Inputs,
+}
+
+impl Witness {
+    pub fn to_bincode(&self) -> Vec<u8> {
+        let body = bincode::serialize(&(self.chain_id, self.block_number, &self.inputs))
+            .expect("bincode serializing inputs failed");
+        [MAGIC.as_slice(), &VERSION.to_le_bytes(), &body].concat()
+    }
+
+    pub fn to_json(&self) -> String {
+        let json = JsonWitness {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            chain_id: self.chain_id,
+            block_number: self.block_number,
+            inputs: self.inputs.clone(),
+        };
+        serde_json::to_string_pretty(&json).expect("json serializing inputs failed")
+    }
+
+    /// Reads either format, telling them apart by the bincode magic.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WitnessError> {
+        match bytes.strip_prefix(MAGIC.as_slice()) {
+            Some(rest) => Self::from_bincode(rest),
+            None => Self::from_json(bytes),
+        }
+    }
+
+    fn from_bincode(bytes: &[u8]) -> Result<Self, WitnessError> {
+        let version = bytes
+            .get(..2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]))
+            .ok_or(WitnessError::Malformed("missing version".to_string()))?;
+        if version != VERSION {
+            return Err(WitnessError::UnsupportedVersion(version));
+        }
+        let (chain_id, block_number, inputs) = bincode::deserialize(&bytes[2..])
+            .map_err(|e| WitnessError::Malformed(e.to_string()))?;
+        Ok(Witness {
+            chain_id,
+            block_number,
+            inputs,
+        })
+    }
+
+    fn from_json(bytes: &[u8]) -> Result<Self, WitnessError> {
+        // check the header first so older layouts fail on version, not shape
+        let header =
+            serde_json::from_slice::<JsonHeader>(bytes).map_err(|_| WitnessError::UnknownFormat)?;
+        match (header.format.as_deref(), header.version) {
+            (Some(FORMAT), Some(VERSION)) => {}
+            (Some(FORMAT), Some(version)) => return Err(WitnessError::UnsupportedVersion(version)),
+            // unversioned JSON written by earlier releases
+            (None, None) => return Err(WitnessError::UnsupportedVersion(0)),
+            _ => return Err(WitnessError::UnknownFormat),
+        }
+        let json = serde_json::from_slice::<JsonWitness>(bytes)
+            .map_err(|e| WitnessError::Malformed(e.to_string()))?;
+        Ok(Witness {
+            chain_id: json.chain_id,
+            block_number: json.block_number,
+            inputs: json.inputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{self, witness};
+
+    #[test]
+    fn test_bincode_roundtrip() {
+        let bytes = witness().to_bincode();
+        assert!(bytes.starts_with(MAGIC));
+        let read = Witness::from_bytes(&bytes).unwrap();
+        assert_eq!(read.block_number, fixtures::BLOCK_NUMBER);
+        assert_eq!(read.inputs.account_proof, witness().inputs.account_proof);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let json = witness().to_json();
+        assert!(json.contains(&format!(r#""safe_address": "{}""#, fixtures::SAFE_ADDRESS)));
+        let read = Witness::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(read.chain_id, fixtures::CHAIN_ID);
+        assert_eq!(read.inputs.header_rlp, witness().inputs.header_rlp);
+    }
+
+    #[test]
+    fn test_rejects_other_versions() {
+        let mut bytes = witness().to_bincode();
+        bytes[MAGIC.len()] = 0;
+        assert!(matches!(
+            Witness::from_bytes(&bytes),
+            Err(WitnessError::UnsupportedVersion(0))
+        ));
+
+        let json = witness()
+            .to_json()
+            .replace(r#""version": 1"#, r#""version": 2"#);
+        assert!(matches!(
+            Witness::from_bytes(json.as_bytes()),
+            Err(WitnessError::UnsupportedVersion(2))
+        ));
+
+        let legacy = r#"{"chain_id":100,"block_number":1,"inputs":{}}"#;
+        assert!(matches!(
+            Witness::from_bytes(legacy.as_bytes()),
+            Err(WitnessError::UnsupportedVersion(0))
+        ));
+        assert!(matches!(
+            Witness::from_bytes(b"garbage"),
+            Err(WitnessError::UnknownFormat)
+        ));
+    }
+}
@@ -0,0 +1,345 @@
+//! Typed client for the sp1-safe server's job API.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use sp1_safe_basics::Sp1SafeParams;
+//! use std::time::Duration;
+//!
+//! let client = sp1_safe_client::Client::new("http://localhost:4190").with_api_key("0a7c...e1");
+//! let job_id = client
+//!     .request_proof(&Sp1SafeParams {
+//!         chain_id: 100,
+//!         safe_address: "0x38Ba7f4278A1482FA0a7bC8B261a9A673336EDDc".to_string(),
+//!         message_hash: "0xa225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7"
+//!             .to_string(),
+//!         callback_url: None,
+//!     })
+//!     .await?;
+//! let result = client.wait_for_proof(&job_id, Duration::from_secs(1800)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use sp1_safe_basics::sealed::{seal_inputs, PublicKey};
+pub use sp1_safe_basics::{Inputs, Progress, PublicValues, Sp1SafeParams, Sp1SafeResult};
+use std::time::Duration;
+
+#[cfg(feature = "verify")]
+pub use sp1_safe_prove::ProverMode;
+
+#[cfg(feature = "verify")]
+const ELF: &[u8] = include_bytes!("../../../program/elf/riscv32im-succinct-zkvm-elf");
+
+#[derive(Deserialize)]
+struct JobCreated {
+    job_id: String,
+}
+
+/// A `POST /witness/sealed` result, which leaves out the Safe address and
+/// message hash.
+#[derive(Deserialize)]
+struct SealedResult {
+    chain_id: u64,
+    block_number: u64,
+    block_hash: String,
+    challenge: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    error: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    base_url: String,
+    api_key: Option<String>,
+    poll_interval: Duration,
+    http: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Client {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            poll_interval: Duration::from_secs(5),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Sent as `authorization: Bearer <key>` to servers requiring api keys.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// How often `wait_for_proof` polls, defaults to 5s.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Submits a proof job, returning its id.
+    pub async fn request_proof(&self, params: &Sp1SafeParams) -> Result<String> {
+        let mut req = self
+            .http
+            .post(format!("{}/jobs", self.base_url))
+            .json(params);
+        if let Some(api_key) = &self.api_key {
+            req = req.bearer_auth(api_key);
+        }
+        let res = checked(req.send().await?).await?;
+        Ok(res.json::<JobCreated>().await?.job_id)
+    }
+
+    /// Proves a locally fetched witness, sealed to the server's X25519
+    /// `public_key` so the Safe address and message hash stay opaque to
+    /// anything between client and server. Pin that key out of band, e.g.
+    /// from the server's operator, as taking it from the same server's
+    /// `GET /witness/key` trusts the very channel sealing guards against.
+    pub async fn prove_sealed(
+        &self,
+        chain_id: u64,
+        inputs: &Inputs,
+        public_key: [u8; 32],
+    ) -> Result<Sp1SafeResult> {
+        let sealed = seal_inputs(&PublicKey::from(public_key), inputs);
+        let mut req = self
+            .http
+            .post(format!("{}/witness/sealed", self.base_url))
+            .query(&[("chain_id", chain_id)])
+            .header("content-type", "application/octet-stream")
+            .body(sealed);
+        if let Some(api_key) = &self.api_key {
+            req = req.bearer_auth(api_key);
+        }
+        let res = checked(req.send().await?).await?;
+        let res = res.json::<SealedResult>().await?;
+        Ok(Sp1SafeResult {
+            chain_id: res.chain_id,
+            safe_address: format!("0x{}", const_hex::encode(inputs.safe_address)),
+            message_hash: format!("0x{}", const_hex::encode(inputs.msg_hash)),
+            block_number: res.block_number,
+            block_hash: res.block_hash,
+            challenge: res.challenge,
+            proof: res.proof,
+        })
+    }
+
+    /// Fetches a job's latest progress event.
+    pub async fn poll(&self, job_id: &str) -> Result<Progress> {
+        let res = self
+            .http
+            .get(format!("{}/jobs/{}", self.base_url, job_id))
+            .send()
+            .await?;
+        Ok(checked(res).await?.json::<Progress>().await?)
+    }
+
+    /// Polls until the job is done, failed or the timeout elapsed.
+    pub async fn wait_for_proof(&self, job_id: &str, timeout: Duration) -> Result<Sp1SafeResult> {
+        let wait = async {
+            loop {
+                match self.poll(job_id).await? {
+                    Progress::Done { result } => return Ok(result),
+                    Progress::Failed { error } => bail!("job {} failed: {}", job_id, error),
+                    _ => tokio::time::sleep(self.poll_interval).await,
+                }
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| anyhow!("timed out waiting for job {}", job_id))?
+    }
+}
+
+/// Turns non-2xx responses into errors carrying the server's error message.
+async fn checked(res: reqwest::Response) -> Result<reqwest::Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    match res.json::<ApiError>().await {
+        Ok(err) => bail!("{}: {}", status, err.error),
+        Err(_) => bail!("{}", status),
+    }
+}
+
+/// Verifies a result's proof without trusting the server, given the
+/// `prover_mode` it runs in as reported by its `GET /status`. Checks first
+/// that the challenge is the one of the result's Safe address and message
+/// hash, else a proof for any other Safe and message would pass.
+#[cfg(feature = "verify")]
+pub fn verify_locally(result: &Sp1SafeResult, mode: ProverMode) -> Result<()> {
+    let public_values = result.checked_public_values()?;
+    let proof = const_hex::decode(&result.proof)?;
+    sp1_safe_prove::Prover::new(ELF, mode).verify(&proof, &public_values.encode())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sp1_safe_basics::{
+        fixtures::{self, params, result},
+        sealed::{SealingKey, StaticSecret},
+    };
+    use wiremock::{
+        http::HeaderName,
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// Mock server answering `verb` requests to `at` with given statuses and
+    /// JSON bodies in order.
+    async fn mock(verb: &str, at: &str, responses: Vec<(u16, String)>) -> MockServer {
+        let server = MockServer::start().await;
+        for (status, body) in responses {
+            Mock::given(method(verb))
+                .and(path(at))
+                .respond_with(ResponseTemplate::new(status).set_body_raw(body, "application/json"))
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+        }
+        server
+    }
+
+    fn event(progress: Progress) -> (u16, String) {
+        (200, serde_json::to_string(&progress).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_proof() {
+        let server = mock(
+            "POST",
+            "/jobs",
+            vec![(202, r#"{"job_id":"abc"}"#.to_string())],
+        )
+        .await;
+        let client = Client::new(server.uri()).with_api_key("secret");
+        assert_eq!(client.request_proof(&params()).await.unwrap(), "abc");
+
+        let req = &server.received_requests().await.unwrap()[0];
+        let auth = req.headers.get(&HeaderName::from("authorization")).unwrap();
+        assert_eq!(auth.last().as_str(), "Bearer secret");
+    }
+
+    #[tokio::test]
+    async fn test_request_proof_rejected() {
+        let server = mock(
+            "POST",
+            "/jobs",
+            vec![(429, r#"{"error":"t(ツ)_/¯ too many requests"}"#.to_string())],
+        )
+        .await;
+        let err = Client::new(server.uri())
+            .request_proof(&params())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("too many requests"));
+    }
+
+    #[tokio::test]
+    async fn test_prove_sealed() {
+        let mut sealed = serde_json::to_value(result()).unwrap();
+        let sealed = sealed.as_object_mut().unwrap();
+        sealed.remove("safe_address");
+        sealed.remove("message_hash");
+        let server = mock(
+            "POST",
+            "/witness/sealed",
+            vec![(200, serde_json::to_string(&sealed).unwrap())],
+        )
+        .await;
+        let key = SealingKey::new(StaticSecret::random());
+        let res = Client::new(server.uri())
+            .prove_sealed(
+                fixtures::CHAIN_ID,
+                &fixtures::inputs(),
+                key.public.to_bytes(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.safe_address, fixtures::SAFE_ADDRESS);
+        assert_eq!(res.message_hash, fixtures::MESSAGE_HASH);
+        assert_eq!(res.challenge, result().challenge);
+
+        let req = &server.received_requests().await.unwrap()[0];
+        assert_eq!(req.url.query(), Some("chain_id=100"));
+        let plaintext = key.open(&req.body).unwrap();
+        let inputs = bincode::deserialize::<Inputs>(&plaintext).unwrap();
+        assert_eq!(inputs.safe_address, fixtures::inputs().safe_address);
+        assert_eq!(inputs.header_rlp, fixtures::inputs().header_rlp);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_proof() {
+        let server = mock(
+            "GET",
+            "/jobs/abc",
+            vec![
+                event(Progress::Queued),
+                event(Progress::Executed { cycles: 8712342 }),
+                event(Progress::Done { result: result() }),
+            ],
+        )
+        .await;
+        let client = Client::new(server.uri()).with_poll_interval(Duration::ZERO);
+        let res = client
+            .wait_for_proof("abc", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(res.challenge, result().challenge);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_proof_failed() {
+        let server = mock(
+            "GET",
+            "/jobs/abc",
+            vec![event(Progress::Failed {
+                error: "t(ツ)_/¯ invalid storage proof".to_string(),
+            })],
+        )
+        .await;
+        let client = Client::new(server.uri()).with_poll_interval(Duration::ZERO);
+        let err = client
+            .wait_for_proof("abc", Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid storage proof"));
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_verify_locally_tampered_safe() {
+        let tampered = Sp1SafeResult {
+            safe_address: "0x000000000000000000000000000000000000dead".to_string(),
+            ..result()
+        };
+        let err = verify_locally(&tampered, ProverMode::Compressed).unwrap_err();
+        assert!(err.to_string().contains("challenge doesn't match"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_proof_timeout() {
+        let server = MockServer::start().await;
+        let (status, body) = event(Progress::Queued);
+        Mock::given(method("GET"))
+            .and(path("/jobs/abc"))
+            .respond_with(ResponseTemplate::new(status).set_body_raw(body, "application/json"))
+            .mount(&server)
+            .await;
+        let client = Client::new(server.uri()).with_poll_interval(Duration::from_millis(50));
+        let err = client
+            .wait_for_proof("abc", Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+}
@@ -1,74 +1,348 @@
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
+use ethereum_trie::{
+    keccak::KeccakHasher, EIP1186Layout, StorageProof, Trie, TrieDBBuilder, H256 as TrieH256,
+};
 use ethers::{
     providers::{Middleware, Provider},
-    types::{Address, Block, H256},
+    types::{Address, Block, EIP1186ProofResponse, Transaction, H256, U256},
 };
-use rlp::RlpStream;
+use futures::future::join_all;
+use rand::seq::SliceRandom;
+use rlp::{Rlp, RlpStream};
 use sp1_safe_basics::{concat_bytes64, keccak256, Inputs, SAFE_SIGNED_MESSAGES_SLOT};
 use zerocopy::AsBytes;
 
 pub async fn fetch_inputs(rpc: &str, safe_address: Address, msg_hash: H256) -> Result<(u64, Inputs)> {
-    let storage_key = keccak256(&concat_bytes64(msg_hash.into(), SAFE_SIGNED_MESSAGES_SLOT));
+    fetch_inputs_with_decoys(rpc, safe_address, msg_hash, 0).await
+}
+
+/// Safe's `execTransaction(...)` selector, transactions calling it target Safes.
+const EXEC_TRANSACTION: [u8; 4] = [0x6a, 0x76, 0x12, 0x02];
+
+/// How many blocks back from the anchor decoys are drawn from at most.
+const DECOY_BLOCKS: u64 = 8;
+
+/// Like `fetch_inputs` but sends the Safe's `eth_getProof` alongside as many
+/// requests for other live contracts, preferably Safes, concurrently in random
+/// order. Decoy storage keys are derived like the Safe's from random message
+/// hashes, so their proven storage is empty where the Safe's is not. The RPC
+/// provider sees that in its responses, thus this does not hide the Safe from
+/// it.
+pub async fn fetch_inputs_with_decoys(
+    rpc: &str,
+    safe_address: Address,
+    msg_hash: H256,
+    decoys: usize,
+) -> Result<(u64, Inputs)> {
+    fetch_inputs_at(rpc, safe_address, msg_hash, None, decoys).await
+}
+
+/// Fetches inputs anchored at given block, defaulting to the latest one.
+pub async fn fetch_inputs_at(
+    rpc: &str,
+    safe_address: Address,
+    msg_hash: H256,
+    block_number: Option<u64>,
+    decoys: usize,
+) -> Result<(u64, Inputs)> {
+    let safe_key = storage_key(msg_hash);
 
     let provider = Provider::try_from(rpc)?;
-    let latest = provider.get_block_number().await?;
+    let latest = match block_number {
+        Some(block_number) => block_number.into(),
+        None => provider.get_block_number().await?,
+    };
     let block = provider.get_block(latest).await?.context("no such block")?;
-    let proof = provider
-        .get_proof(safe_address, vec![storage_key.into()], Some(latest.into()))
-        .await?;
+    let mut queries = Vec::new();
+    if decoys > 0 {
+        let mut blocks = Vec::new();
+        for number in (latest.as_u64().saturating_sub(DECOY_BLOCKS - 1)..=latest.as_u64()).rev() {
+            blocks.extend(provider.get_block_with_txs(number).await?);
+            if decoy_accounts(&blocks, safe_address).len() >= decoys {
+                break;
+            }
+        }
+        let accounts = decoy_accounts(&blocks, safe_address);
+        if accounts.len() < decoys {
+            log::warn!("🎭 only {} of {} decoys available", accounts.len(), decoys);
+        }
+        queries.extend(
+            accounts
+                .into_iter()
+                .take(decoys)
+                .map(|address| (address, storage_key(H256::random()))),
+        );
+    }
+    queries.push((safe_address, safe_key));
+    queries.shuffle(&mut rand::thread_rng());
+    let proofs = join_all(
+        queries
+            .iter()
+            .map(|(address, key)| provider.get_proof(*address, vec![*key], Some(latest.into()))),
+    )
+    .await;
+    let mut proof = None;
+    for ((address, _), res) in queries.iter().zip(proofs) {
+        match res {
+            _ if *address == safe_address => proof = Some(res),
+            // the Safe's proof is still valid, but its request stood out
+            Err(err) => log::warn!("🎭 decoy proof failed: {}", err),
+            Ok(_) => {}
+        }
+    }
+    let proof = proof.context("no proof")??;
 
     Ok((
         latest.as_u64(),
-        Inputs {
-            safe_address: safe_address.into(),
-            msg_hash: msg_hash.into(),
-            header_rlp: rlp_encode_header(&block),
-            state_root: block.state_root.into(),
-            storage_root: proof.storage_hash.into(),
-            state_trie_key: keccak256(&safe_address),
-            storage_trie_key: keccak256(&storage_key),
-            account_proof: proof
-                .account_proof
-                .iter()
-                .map(|b| b.as_bytes().to_vec())
-                .collect(),
-            storage_proof: proof.storage_proof[0]
-                .proof
-                .iter()
-                .map(|b| b.as_bytes().to_vec())
-                .collect(),
-        },
+        build_inputs(safe_address, msg_hash, &block, &proof)?,
     ))
 }
 
+/// Decoy accounts called by given blocks' transactions except `exclude`,
+/// Safes targeted by `execTransaction` first, then other contracts receiving
+/// calldata, either group shuffled. EOAs are skipped as their empty storage
+/// would set them apart from a Safe.
+pub fn decoy_accounts(blocks: &[Block<Transaction>], exclude: Address) -> Vec<Address> {
+    let mut safes = Vec::new();
+    let mut contracts = Vec::new();
+    for tx in blocks.iter().flat_map(|block| &block.transactions) {
+        let to = match tx.to {
+            Some(to) if to != exclude && !tx.input.is_empty() => to,
+            _ => continue,
+        };
+        if tx.input.starts_with(&EXEC_TRANSACTION) {
+            safes.push(to);
+        } else {
+            contracts.push(to);
+        }
+    }
+    let mut rng = rand::thread_rng();
+    for group in [&mut safes, &mut contracts] {
+        group.sort();
+        group.dedup();
+        group.shuffle(&mut rng);
+    }
+    contracts.retain(|address| !safes.contains(address));
+    safes.extend(contracts);
+    safes
+}
+
+/// Storage key of a message in the Safe's `signedMessages` mapping, the one to
+/// request `eth_getProof` for.
+pub fn storage_key(msg_hash: H256) -> H256 {
+    keccak256(concat_bytes64(msg_hash.into(), SAFE_SIGNED_MESSAGES_SLOT)).into()
+}
+
+/// Assembles inputs from an `eth_getBlockBy*` block and the Safe's
+/// `eth_getProof` response for `storage_key(msg_hash)` at that block, so
+/// callers with their own RPC access, like browsers, needn't fetch via us.
+pub fn build_inputs(
+    safe_address: Address,
+    msg_hash: H256,
+    block: &Block<H256>,
+    proof: &EIP1186ProofResponse,
+) -> Result<Inputs> {
+    let storage_key = storage_key(msg_hash);
+    ensure!(proof.address == safe_address, "proof for another account");
+    let storage_proof = proof
+        .storage_proof
+        .iter()
+        .find(|p| p.key == U256::from_big_endian(storage_key.as_bytes()))
+        .context("no storage proof for the message")?;
+
+    Ok(Inputs {
+        safe_address: safe_address.into(),
+        msg_hash: msg_hash.into(),
+        header_rlp: rlp_encode_header(block)?,
+        state_root: block.state_root.into(),
+        storage_root: proof.storage_hash.into(),
+        state_trie_key: keccak256(safe_address),
+        storage_trie_key: keccak256(storage_key),
+        account_proof: proof
+            .account_proof
+            .iter()
+            .map(|b| b.as_bytes().to_vec())
+            .collect(),
+        storage_proof: storage_proof
+            .proof
+            .iter()
+            .map(|b| b.as_bytes().to_vec())
+            .collect(),
+    })
+}
+
 // https://ethereum.stackexchange.com/a/67332
 // https://github.com/ethereum/go-ethereum/blob/14eb8967be7acc54c5dc9a416151ac45c01251b6/core/types/block.go#L65
-pub fn rlp_encode_header(block: &Block<H256>) -> Vec<u8> {
+/// Errors on blocks missing a field of the Cancun header layout, fx pending
+/// blocks or ones from before a fork that added fields.
+pub fn rlp_encode_header(block: &Block<H256>) -> Result<Vec<u8>> {
     let mut rlp = RlpStream::new();
     rlp.begin_list(20);
     rlp.append(&block.parent_hash);
     rlp.append(&block.uncles_hash);
-    rlp.append(&block.author.expect("author"));
+    rlp.append(&block.author.context("block lacks author")?);
     rlp.append(&block.state_root);
     rlp.append(&block.transactions_root);
     rlp.append(&block.receipts_root);
-    rlp.append(&block.logs_bloom.expect("logs_bloom"));
+    rlp.append(&block.logs_bloom.context("block lacks logs_bloom")?);
     rlp.append(&block.difficulty);
-    rlp.append(&block.number.expect("number"));
+    rlp.append(&block.number.context("block lacks number")?);
     rlp.append(&block.gas_limit);
     rlp.append(&block.gas_used);
     rlp.append(&block.timestamp);
     rlp.append(&block.extra_data.as_bytes().to_vec());
-    rlp.append(&block.mix_hash.expect("mix_hash"));
-    rlp.append(&block.nonce.expect("nonce"));
-    rlp.append(&block.base_fee_per_gas.expect("base_fee_per_gas")); // london
-    rlp.append(&block.withdrawals_root.expect("withdrawals_root")); // shanghai
-    rlp.append(&block.blob_gas_used.expect("blob_gas_used")); // cancun
-    rlp.append(&block.excess_blob_gas.expect("excess_blob_gas")); // cancun
+    rlp.append(&block.mix_hash.context("block lacks mix_hash")?);
+    rlp.append(&block.nonce.context("block lacks nonce")?);
+    rlp.append(
+        &block
+            .base_fee_per_gas
+            .context("block lacks base_fee_per_gas, pre-london?")?,
+    );
+    rlp.append(
+        &block
+            .withdrawals_root
+            .context("block lacks withdrawals_root, pre-shanghai?")?,
+    );
+    rlp.append(
+        &block
+            .blob_gas_used
+            .context("block lacks blob_gas_used, pre-cancun?")?,
+    );
+    rlp.append(
+        &block
+            .excess_blob_gas
+            .context("block lacks excess_blob_gas, pre-cancun?")?,
+    );
     rlp.append(
         &block
             .parent_beacon_block_root
-            .expect("parent_beacon_block_root"),
-    ); // cancun
-    rlp.out().freeze().into()
+            .context("block lacks parent_beacon_block_root, pre-cancun?")?,
+    );
+    Ok(rlp.out().freeze().into())
+}
+
+/// Reads the block number, the 9th header field, from an RLP-encoded header.
+pub fn header_block_number(header_rlp: &[u8]) -> Result<u64> {
+    Ok(Rlp::new(header_rlp).val_at::<u64>(8)?)
+}
+
+/// Natively runs the same checks as the zkVM program so invalid client-supplied
+/// witnesses get rejected before burning any proving time.
+pub fn verify_inputs(inputs: &Inputs) -> Result<()> {
+    let storage_key = keccak256(concat_bytes64(inputs.msg_hash, SAFE_SIGNED_MESSAGES_SLOT));
+    ensure!(
+        inputs.storage_trie_key == keccak256(storage_key),
+        "storage trie key mismatch"
+    );
+    ensure!(
+        inputs.state_trie_key == keccak256(inputs.safe_address),
+        "state trie key mismatch"
+    );
+    ensure!(
+        inputs.header_rlp.get(91..123) == Some(&inputs.state_root[..]),
+        "header state root mismatch"
+    );
+
+    let storage_root = TrieH256(inputs.storage_root);
+    let storage_db =
+        StorageProof::new(inputs.storage_proof.clone()).into_memory_db::<KeccakHasher>();
+    let storage_trie =
+        TrieDBBuilder::<EIP1186Layout<KeccakHasher>>::new(&storage_db, &storage_root).build();
+    let storage_val = storage_trie
+        .get(&inputs.storage_trie_key)
+        .map_err(|e| anyhow::anyhow!("storage trie read failed: {:?}", e))?
+        .context("target storage node is none")?;
+    // Safe's SignMessageLib marks messages as "signed" with a literal 1
+    ensure!(storage_val.first() == Some(&1u8), "msg not signed");
+
+    let state_root = TrieH256(inputs.state_root);
+    let state_db = StorageProof::new(inputs.account_proof.clone()).into_memory_db::<KeccakHasher>();
+    let state_trie =
+        TrieDBBuilder::<EIP1186Layout<KeccakHasher>>::new(&state_db, &state_root).build();
+    let proof_ok = state_trie
+        .contains(&inputs.state_trie_key)
+        .map_err(|e| anyhow::anyhow!("account check failed: {:?}", e))?;
+    ensure!(proof_ok, "storage proof verification failed");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sp1_safe_basics::fixtures;
+
+    // eth_getBlockByNumber and eth_getProof responses for a signed message
+    // on Gnosis
+    const BLOCK: &str = include_str!("../fixtures/gnosis-33073608-block.json");
+    const PROOF: &str = include_str!("../fixtures/gnosis-33073608-proof.json");
+
+    fn safe_address() -> Address {
+        fixtures::SAFE_ADDRESS.parse().unwrap()
+    }
+
+    fn msg_hash() -> H256 {
+        fixtures::MESSAGE_HASH.parse().unwrap()
+    }
+
+    #[test]
+    fn test_build_inputs() {
+        let block = serde_json::from_str::<Block<H256>>(BLOCK).unwrap();
+        let proof = serde_json::from_str::<EIP1186ProofResponse>(PROOF).unwrap();
+        let inputs = build_inputs(safe_address(), msg_hash(), &block, &proof).unwrap();
+        assert_eq!(
+            header_block_number(&inputs.header_rlp).unwrap(),
+            fixtures::BLOCK_NUMBER
+        );
+        assert_eq!(keccak256(&inputs.header_rlp), block.hash.unwrap().0);
+        verify_inputs(&inputs).unwrap();
+        // the shared witness fixture is derived from these responses
+        let fixture = fixtures::inputs();
+        assert_eq!(inputs.header_rlp, fixture.header_rlp);
+        assert_eq!(inputs.account_proof, fixture.account_proof);
+        assert_eq!(inputs.storage_proof, fixture.storage_proof);
+        assert_eq!(inputs.storage_trie_key, fixture.storage_trie_key);
+    }
+
+    #[test]
+    fn test_decoy_accounts() {
+        let tx = |to: Address, input: &[u8]| Transaction {
+            to: Some(to),
+            input: input.to_vec().into(),
+            ..Default::default()
+        };
+        let (safe, contract, eoa) = (Address::random(), Address::random(), Address::random());
+        let block = Block {
+            transactions: vec![
+                tx(contract, &[0xa9, 0x05, 0x9c, 0xbb]),
+                tx(eoa, &[]),
+                tx(safe, &EXEC_TRANSACTION),
+                tx(safe_address(), &EXEC_TRANSACTION),
+                tx(safe, &EXEC_TRANSACTION),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            decoy_accounts(&[block], safe_address()),
+            vec![safe, contract]
+        );
+    }
+
+    #[test]
+    fn test_rlp_encode_header_missing_field() {
+        let mut block = serde_json::from_str::<Block<H256>>(BLOCK).unwrap();
+        let proof = serde_json::from_str::<EIP1186ProofResponse>(PROOF).unwrap();
+        block.parent_beacon_block_root = None;
+        let err = rlp_encode_header(&block).unwrap_err();
+        assert!(err.to_string().contains("parent_beacon_block_root"));
+        assert!(build_inputs(safe_address(), msg_hash(), &block, &proof).is_err());
+    }
+
+    #[test]
+    fn test_build_inputs_mismatch() {
+        let block = serde_json::from_str::<Block<H256>>(BLOCK).unwrap();
+        let proof = serde_json::from_str::<EIP1186ProofResponse>(PROOF).unwrap();
+        assert!(build_inputs(Address::random(), msg_hash(), &block, &proof).is_err());
+        assert!(build_inputs(safe_address(), H256::random(), &block, &proof).is_err());
+    }
 }
@@ -0,0 +1,221 @@
+//! Proving pipeline shared by the server and script, switchable via
+//! `ProverMode` to trade proof strength for speed during development:
+//!
+//! - `execute` only runs the program yielding an empty mock proof but the real
+//!   public values
+//! - `core` proves a sharded STARK
+//! - `compressed` additionally recursively compresses that to a single STARK
+//! - `plonk` additionally wraps that in a PLONK proof verifiable on-chain,
+//!   the default, only available with the `plonk` feature
+
+use anyhow::{anyhow, bail, Result};
+#[cfg(feature = "plonk")]
+use sp1_sdk::SP1PlonkBn254Proof;
+use sp1_sdk::{
+    ProverClient, SP1CompressedProof, SP1Proof, SP1ProofWithPublicValues, SP1ProvingKey,
+    SP1PublicValues, SP1Stdin, SP1VerifyingKey,
+};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ProverMode {
+    Execute,
+    Core,
+    Compressed,
+    #[default]
+    Plonk,
+}
+
+impl FromStr for ProverMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "execute" => Ok(ProverMode::Execute),
+            "core" => Ok(ProverMode::Core),
+            "compressed" => Ok(ProverMode::Compressed),
+            "plonk" => Ok(ProverMode::Plonk),
+            _ => bail!("invalid prover mode {}", s),
+        }
+    }
+}
+
+impl fmt::Display for ProverMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProverMode::Execute => "execute",
+            ProverMode::Core => "core",
+            ProverMode::Compressed => "compressed",
+            ProverMode::Plonk => "plonk",
+        })
+    }
+}
+
+/// Pipeline stages reported once completed.
+#[derive(Clone, Copy, Debug)]
+pub enum Stage {
+    Executed { cycles: u64 },
+    CoreProved,
+    Compressed,
+    PlonkWrapped,
+}
+
+pub struct Proven {
+    pub mode: ProverMode,
+    pub public_values: SP1PublicValues,
+    /// bincode-encoded proof of the mode's type, empty for `execute`
+    pub proof: Vec<u8>,
+}
+
+pub struct Prover {
+    pub client: ProverClient,
+    pub pk: SP1ProvingKey,
+    pub vk: SP1VerifyingKey,
+    pub mode: ProverMode,
+}
+
+impl Prover {
+    pub fn new(elf: &[u8], mode: ProverMode) -> Self {
+        let client = ProverClient::new();
+        let (pk, vk) = client.setup(elf);
+        Prover {
+            client,
+            pk,
+            vk,
+            mode,
+        }
+    }
+
+    /// Runs the pipeline up to the configured mode, same steps as
+    /// `ProverClient::prove_plonk` but observable.
+    pub fn prove(&self, stdin: SP1Stdin, mut on_stage: impl FnMut(Stage)) -> Result<Proven> {
+        self.try_prove(stdin, |stage| {
+            on_stage(stage);
+            Ok(())
+        })
+    }
+
+    /// Like `prove` but stops after the stage for which `on_stage` errors,
+    /// returning that error, e.g. to cancel proving. Always executes the ELF
+    /// the proving key was set up from. Without the `plonk` feature fails
+    /// up front in plonk mode.
+    pub fn try_prove(
+        &self,
+        stdin: SP1Stdin,
+        mut on_stage: impl FnMut(Stage) -> Result<()>,
+    ) -> Result<Proven> {
+        #[cfg(not(feature = "plonk"))]
+        if self.mode == ProverMode::Plonk {
+            return plonk_unavailable(self.mode);
+        }
+        let (public_values, report) = self
+            .client
+            .execute(&self.pk.elf, stdin.clone())
+            .map_err(|e| anyhow!("execution failed: {}", e))?;
+        on_stage(Stage::Executed {
+            cycles: report.total_instruction_count(),
+        })?;
+        if self.mode == ProverMode::Execute {
+            return Ok(Proven {
+                mode: self.mode,
+                public_values,
+                proof: Vec::new(),
+            });
+        }
+
+        let prover = self.client.prover.sp1_prover();
+        let core = prover
+            .prove_core(&self.pk, &stdin)
+            .map_err(|e| anyhow!("proving failed: {:?}", e))?;
+        let public_values = core.public_values.clone();
+        on_stage(Stage::CoreProved)?;
+        if self.mode == ProverMode::Core {
+            return Ok(Proven {
+                mode: self.mode,
+                public_values,
+                proof: bincode::serialize(&core.proof)?,
+            });
+        }
+
+        let compressed = prover
+            .compress(&self.vk, core, vec![])
+            .map_err(|e| anyhow!("compressing failed: {:?}", e))?;
+        on_stage(Stage::Compressed)?;
+        if self.mode == ProverMode::Compressed {
+            return Ok(Proven {
+                mode: self.mode,
+                public_values,
+                proof: bincode::serialize(&compressed)?,
+            });
+        }
+
+        #[cfg(feature = "plonk")]
+        let proof = {
+            let shrunk = prover
+                .shrink(compressed)
+                .map_err(|e| anyhow!("shrinking failed: {:?}", e))?;
+            let wrapped = prover
+                .wrap_bn254(shrunk)
+                .map_err(|e| anyhow!("wrapping failed: {:?}", e))?;
+            let artifacts = sp1_sdk::install::try_install_plonk_bn254_artifacts();
+            prover.wrap_plonk_bn254(wrapped, &artifacts)
+        };
+        #[cfg(not(feature = "plonk"))]
+        let proof: Vec<u8> = plonk_unavailable(compressed)?;
+        on_stage(Stage::PlonkWrapped)?;
+
+        Ok(Proven {
+            mode: self.mode,
+            public_values,
+            proof: bincode::serialize(&proof)?,
+        })
+    }
+
+    /// Verifies a bincode-encoded proof of the configured mode against given
+    /// public values, which the proof commits to.
+    pub fn verify(&self, proof: &[u8], public_values: &[u8]) -> Result<()> {
+        let public_values = SP1PublicValues::from(public_values);
+        // verification never reads the stdin, only checks the proof against
+        // the vk and that it commits to the digest of given public values, so
+        // an empty one is sound and keeps witnesses out of proofs
+        let stdin = SP1Stdin::new();
+        let res = match self.mode {
+            ProverMode::Execute => bail!("execute mode yields no proof to verify"),
+            ProverMode::Core => {
+                let proof: SP1Proof = SP1ProofWithPublicValues {
+                    proof: bincode::deserialize(proof)?,
+                    stdin,
+                    public_values,
+                };
+                self.client.verify(&proof, &self.vk)
+            }
+            ProverMode::Compressed => {
+                let proof: SP1CompressedProof = SP1ProofWithPublicValues {
+                    proof: bincode::deserialize(proof)?,
+                    stdin,
+                    public_values,
+                };
+                self.client.verify_compressed(&proof, &self.vk)
+            }
+            #[cfg(not(feature = "plonk"))]
+            ProverMode::Plonk => return plonk_unavailable(proof),
+            #[cfg(feature = "plonk")]
+            ProverMode::Plonk => {
+                let proof: SP1PlonkBn254Proof = SP1ProofWithPublicValues {
+                    proof: bincode::deserialize(proof)?,
+                    stdin,
+                    public_values,
+                };
+                self.client.verify_plonk(&proof, &self.vk)
+            }
+        };
+        res.map_err(|e| anyhow!("verification failed: {:?}", e))
+    }
+}
+
+/// Stands in for PLONK wrapping and verification without the `plonk` feature.
+#[cfg(not(feature = "plonk"))]
+fn plonk_unavailable<T, U>(_: T) -> Result<U> {
+    bail!("plonk mode needs sp1-safe-prove's plonk feature")
+}
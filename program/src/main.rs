@@ -11,16 +11,14 @@
 //! challenge point that allows associating given proof to a particular
 //! Safe and message by recomputing the hash given the account and storage
 //! keys. The message hash must incorporate a nullifier to guard against
-//! rainbow table precomputations.
+//! rainbow table precomputations. Both outputs get committed as versioned
+//! `PublicValues`.
 
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use ark_bn254::Fr;
-use ark_ff::{BigInteger, PrimeField};
 use ethereum_trie::{keccak::KeccakHasher, EIP1186Layout, StorageProof, Trie, TrieDBBuilder, H256};
-use light_poseidon::{Poseidon, PoseidonHasher};
-use sp1_safe_basics::{keccak256, lpad_bytes32, Inputs};
+use sp1_safe_basics::{compute_challenge, keccak256, Inputs, PublicValues};
 
 pub fn main() {
     let inputs = sp1_zkvm::io::read::<Inputs>();
@@ -52,21 +50,7 @@ pub fn main() {
     header_rlp[91..123].copy_from_slice(state_root.as_bytes());
     let blockhash = keccak256(&header_rlp);
 
-    let mut poseidon = Poseidon::<Fr>::new_circom(2).expect("poseidon init failed");
-    // _mod_order might reduce fr2 i.e. it has 2 msg_hash preimages aka collision;
-    // since the 20-byte Safe address cannot exceed bn254's scalar field _mod_order
-    // is always a noop for fr1, i.e. it has strictly 1 Safe address preimage: 
-    // no collisions; consequently "cross-account" collisions can never occur
-    let fr1 = Fr::from_be_bytes_mod_order(&lpad_bytes32(inputs.safe_address));
-    let fr2 = Fr::from_be_bytes_mod_order(&inputs.msg_hash);
-    let challenge: [u8; 32] = poseidon
-        .hash(&[fr1, fr2])
-        .expect("poseidon hash failed")
-        .into_bigint()
-        .to_bytes_be()
-        .try_into()
-        .expect("converting field elements to bytes failed");
+    let challenge = compute_challenge(inputs.safe_address, inputs.msg_hash);
 
-    sp1_zkvm::io::commit_slice(&blockhash);
-    sp1_zkvm::io::commit_slice(&challenge);
+    sp1_zkvm::io::commit_slice(&PublicValues::new(blockhash, challenge).encode());
 }
@@ -1,60 +1,253 @@
-//! A simple script to generate the proof of the sp1-safe program.
+//! `sp1-safe` CLI fetching witnesses for, executing and proving the sp1-safe
+//! program, and verifying its proofs. Results are JSON, written to `--out` or
+//! stdout.
 
-use const_hex;
-use serde_json::json;
-use sp1_safe_basics::{Inputs, Sp1SafeResult};
-use sp1_safe_fetch::fetch_inputs;
-use sp1_sdk::{ProverClient, SP1Stdin/*, HashableKey*/};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use sp1_safe_basics::{compute_challenge, witness::Witness, Inputs, PublicValues, Sp1SafeResult};
+use sp1_safe_fetch::fetch_inputs_at;
+use sp1_safe_prove::{Prover, ProverMode, Stage};
+use sp1_sdk::{HashableKey, SP1Stdin};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 const ELF: &[u8] = include_bytes!("../../program/elf/riscv32im-succinct-zkvm-elf");
 
+#[derive(Parser)]
+#[command(
+    name = "sp1-safe",
+    version,
+    about = "Prove a Safe multisig over a message in zk"
+)]
+struct Cli {
+    /// File to write JSON output to instead of stdout
+    #[arg(long, global = true)]
+    out: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch a witness, i.e. the program's inputs, for proving elsewhere
+    Fetch {
+        #[command(flatten)]
+        target: Target,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    /// Execute the program without proving, reporting cycles and outputs
+    Execute(Source),
+    /// Prove the program
+    Prove {
+        #[command(flatten)]
+        source: Source,
+        #[arg(long, env = "PROVER_MODE", default_value_t = ProverMode::Plonk)]
+        mode: ProverMode,
+    },
+    /// Verify a proof written by `prove` and that its challenge matches its Safe
+    /// and message
+    Verify {
+        /// Result JSON written by `prove`
+        result: PathBuf,
+        /// Mode the proof was generated in
+        #[arg(long, env = "PROVER_MODE", default_value_t = ProverMode::Plonk)]
+        mode: ProverMode,
+    },
+    /// Compute the challenge binding a proof to a Safe and message
+    Challenge {
+        #[arg(long, env = "SAFE")]
+        safe: String,
+        #[arg(long, env = "MSG_HASH")]
+        msg_hash: String,
+    },
+    /// Print the program's verifying key hash
+    Vkey,
+}
+
+#[derive(Args)]
+struct Target {
+    /// Chain id, picks a default RPC for Gnosis (100) and Sepolia (11155111)
+    #[arg(long, default_value_t = 100)]
+    chain_id: u64,
+    #[arg(long, env = "RPC")]
+    rpc: Option<String>,
+    #[arg(long, env = "SAFE")]
+    safe: String,
+    #[arg(long, env = "MSG_HASH")]
+    msg_hash: String,
+    /// Block to anchor the storage proof at, defaults to latest
+    #[arg(long)]
+    block: Option<u64>,
+}
+
+#[derive(Args)]
+struct Source {
+    /// Witness file written by `fetch` to use instead of fetching, either format
+    #[arg(long)]
+    witness: Option<PathBuf>,
+    #[arg(long, default_value_t = 100)]
+    chain_id: u64,
+    #[arg(long, env = "RPC")]
+    rpc: Option<String>,
+    #[arg(long, env = "SAFE", required_unless_present = "witness")]
+    safe: Option<String>,
+    #[arg(long, env = "MSG_HASH", required_unless_present = "witness")]
+    msg_hash: Option<String>,
+    #[arg(long)]
+    block: Option<u64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Human-readable with hex fields
+    Json,
+    /// Compact
+    Bincode,
+}
+
+impl Target {
+    async fn fetch(&self) -> Result<Witness> {
+        let rpc = match (&self.rpc, self.chain_id) {
+            (Some(rpc), _) => rpc.clone(),
+            (None, 100) => "https://rpc.gnosis.gateway.fm".to_string(),
+            (None, 11155111) => "https://1rpc.io/sepolia".to_string(),
+            (None, chain_id) => bail!("--rpc required for chain id {}", chain_id),
+        };
+        let safe = const_hex::decode_to_array::<&str, 20>(&self.safe).context("invalid --safe")?;
+        let msg_hash =
+            const_hex::decode_to_array::<&str, 32>(&self.msg_hash).context("invalid --msg-hash")?;
+        let (block_number, inputs) =
+            fetch_inputs_at(&rpc, safe.into(), msg_hash.into(), self.block, 0).await?;
+        Ok(Witness {
+            chain_id: self.chain_id,
+            block_number,
+            inputs,
+        })
+    }
+}
+
+impl Source {
+    async fn witness(self) -> Result<Witness> {
+        match self.witness {
+            Some(path) => {
+                let bytes = std::fs::read(&path)
+                    .with_context(|| format!("failed reading {}", path.display()))?;
+                Ok(Witness::from_bytes(&bytes)?)
+            }
+            None => {
+                Target {
+                    chain_id: self.chain_id,
+                    rpc: self.rpc,
+                    safe: self.safe.context("--safe required")?,
+                    msg_hash: self.msg_hash.context("--msg-hash required")?,
+                    block: self.block,
+                }
+                .fetch()
+                .await
+            }
+        }
+    }
+}
+
+fn hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", const_hex::encode(bytes))
+}
+
+fn prove(witness: &Witness, mode: ProverMode) -> Result<(u64, PublicValues, Vec<u8>)> {
+    let mut stdin = SP1Stdin::new();
+    stdin.write::<Inputs>(&witness.inputs);
+    let mut cycles = 0;
+    let proven = Prover::new(ELF, mode).prove(stdin, |stage| {
+        if let Stage::Executed { cycles: n } = stage {
+            cycles = n;
+            log::info!("cycles {}", n);
+        }
+    })?;
+    let public_values = PublicValues::decode(proven.public_values.as_slice())?;
+    Ok((cycles, public_values, proven.proof))
+}
+
+fn write(out: Option<&Path>, bytes: &[u8]) -> Result<()> {
+    match out {
+        Some(path) => std::fs::write(path, bytes)
+            .with_context(|| format!("failed writing {}", path.display())),
+        None => Ok(std::io::stdout().write_all(bytes)?),
+    }
+}
+
+fn emit(out: Option<&Path>, value: Value) -> Result<()> {
+    let json = serde_json::to_string_pretty(&value)?;
+    write(out, (json + "\n").as_bytes())
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<()> {
     sp1_sdk::utils::setup_logger();
-    // Assemble and write inputs
-    let rpc = std::env::var("RPC").unwrap_or("https://rpc.gnosis.gateway.fm".to_string());
-    let safe = const_hex::decode_to_array::<&str, 20>(
-        &std::env::var("SAFE").expect("must set env var SAFE=0x..."),
-    )
-    .expect("env var SAFE");
-    let msg_hash = const_hex::decode_to_array::<&str, 32>(
-        &std::env::var("MSG_HASH").expect("must set env var MSG_HASH=0x..."),
-    )
-    .expect("env var MSG_HASH");
-    let (anchor, inputs) = fetch_inputs(&rpc, safe.into(), msg_hash.into())
-        .await
-        .expect("fetch_inputs failed");
-    let mut stdin = SP1Stdin::new();
-    stdin.write::<Inputs>(&inputs);
-
-    // Generate proof
-    let client = ProverClient::new();
-    let (pk, _vk) = client.setup(ELF);
-    // println!("✞✞✞✞✞ vk {}", const_hex::encode(&_vk.hash_bytes()));
-    let mut proofwpv = client.prove_plonk(&pk, stdin).expect("proving failed");
-
-    let blockhash = proofwpv.public_values.read::<[u8; 32]>();
-    let challenge = proofwpv.public_values.read::<[u8; 32]>();
-
-    println!(
-        "{}",
-        json!(Sp1SafeResult {
-            chain_id: 100,
-            safe_address: format!("0x{}", const_hex::encode(safe)),
-            message_hash: format!("0x{}", const_hex::encode(msg_hash)),
-            block_number: anchor,
-            block_hash: format!("0x{}", const_hex::encode(blockhash)),
-            challenge: format!("0x{}", const_hex::encode(challenge)),
-            proof: format!(
-                "0x{}",
-                const_hex::encode(bincode::serialize(&proofwpv.proof).expect("bincode"))
-            ),
-        })
-        .to_string()
-    );
+    let cli = Cli::parse();
+    let out = cli.out.as_deref();
 
-    // // Verify proof and public values
-    // client
-    //     .verify_plonk(&proofwpv, &vk)
-    //     .expect("verification failed");
+    match cli.command {
+        Command::Fetch { target, format } => {
+            let witness = target.fetch().await?;
+            match format {
+                Format::Json => write(out, (witness.to_json() + "\n").as_bytes()),
+                Format::Bincode => write(out, &witness.to_bincode()),
+            }
+        }
+        Command::Execute(source) => {
+            let witness = source.witness().await?;
+            let (cycles, public_values, _) = prove(&witness, ProverMode::Execute)?;
+            emit(
+                out,
+                json!({
+                    "chain_id": witness.chain_id,
+                    "block_number": witness.block_number,
+                    "cycles": cycles,
+                    "public_values_version": public_values.version,
+                    "block_hash": hex(public_values.block_hash),
+                    "challenge": hex(public_values.challenge),
+                }),
+            )
+        }
+        Command::Prove { source, mode } => {
+            let witness = source.witness().await?;
+            let (_, public_values, proof) = prove(&witness, mode)?;
+            emit(
+                out,
+                json!(Sp1SafeResult {
+                    chain_id: witness.chain_id,
+                    safe_address: hex(witness.inputs.safe_address),
+                    message_hash: hex(witness.inputs.msg_hash),
+                    block_number: witness.block_number,
+                    block_hash: hex(public_values.block_hash),
+                    challenge: hex(public_values.challenge),
+                    proof: hex(proof),
+                }),
+            )
+        }
+        Command::Verify { result, mode } => {
+            let json = std::fs::read(&result)
+                .with_context(|| format!("failed reading {}", result.display()))?;
+            let result: Sp1SafeResult = serde_json::from_slice(&json)?;
+            let public_values = result.checked_public_values()?;
+            Prover::new(ELF, mode)
+                .verify(&const_hex::decode(&result.proof)?, &public_values.encode())?;
+            emit(out, json!({ "verified": true, "mode": mode.to_string() }))
+        }
+        Command::Challenge { safe, msg_hash } => {
+            let safe = const_hex::decode_to_array::<&str, 20>(&safe).context("invalid --safe")?;
+            let msg_hash =
+                const_hex::decode_to_array::<&str, 32>(&msg_hash).context("invalid --msg-hash")?;
+            emit(
+                out,
+                json!({ "challenge": hex(compute_challenge(safe, msg_hash)) }),
+            )
+        }
+        Command::Vkey => {
+            let prover = Prover::new(ELF, ProverMode::default());
+            emit(out, json!({ "vkey_hash": hex(prover.vk.hash_bytes()) }))
+        }
+    }
 }
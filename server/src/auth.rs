@@ -0,0 +1,334 @@
+//! Optional bearer API key authentication with per-key rate limits and
+//! concurrency quotas. Enabled by pointing setting `api_keys_file` at a TOML
+//! file like below, the file is read at startup so quotas can be changed by
+//! restarting the server without rebuilding it.
+//!
+//! ```toml
+//! [[keys]]
+//! key = "0a7c...e1"
+//! rate_limit = 6      # max requests per window
+//! window_secs = 3600  # sliding window length
+//! max_concurrent = 1  # max proofs in flight
+//! ```
+
+use anyhow::{bail, Context, Result};
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    figment::{
+        providers::{Format, Toml},
+        Figment,
+    },
+    http::{Header, Status},
+    request::{FromRequest, Outcome, Request},
+    serde::Deserialize,
+    Build, Response, Rocket,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct KeySpec {
+    pub key: String,
+    #[serde(default = "default_rate_limit")]
+    pub rate_limit: usize,
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+fn default_rate_limit() -> usize {
+    10
+}
+
+fn default_window_secs() -> u64 {
+    3600
+}
+
+fn default_max_concurrent() -> usize {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct KeysFile {
+    keys: Vec<KeySpec>,
+}
+
+struct Quota {
+    spec: KeySpec,
+    hits: VecDeque<Instant>,
+    active: usize,
+}
+
+/// Why a key was denied, carrying the `retry-after` seconds.
+enum Denied {
+    RateLimited(u64),
+    Busy(u64),
+}
+
+/// Keys are looked up by their SHA-256 digest so lookups don't compare
+/// secret bytes, leaking matching prefixes through timing.
+type KeyDigest = [u8; 32];
+
+fn digest(key: &str) -> KeyDigest {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+/// Registry of API keys and their current usage.
+pub struct Keys(Mutex<HashMap<KeyDigest, Quota>>);
+
+impl Keys {
+    pub fn new(specs: Vec<KeySpec>) -> Self {
+        Keys(Mutex::new(
+            specs
+                .into_iter()
+                .map(|spec| {
+                    (
+                        digest(&spec.key),
+                        Quota {
+                            spec,
+                            hits: VecDeque::new(),
+                            active: 0,
+                        },
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+    fn acquire(&self, key: &KeyDigest) -> Option<Result<(), Denied>> {
+        let mut keys = self.0.lock().expect("keys lock poisoned");
+        let quota = keys.get_mut(key)?;
+        let now = Instant::now();
+        let window = Duration::from_secs(quota.spec.window_secs);
+        while quota
+            .hits
+            .front()
+            .is_some_and(|hit| now.duration_since(*hit) >= window)
+        {
+            quota.hits.pop_front();
+        }
+        if quota.hits.len() >= quota.spec.rate_limit {
+            let oldest = quota.hits.front().copied().unwrap_or(now);
+            let wait = window.saturating_sub(now.duration_since(oldest));
+            return Some(Err(Denied::RateLimited(wait.as_secs().max(1))));
+        }
+        if quota.active >= quota.spec.max_concurrent {
+            // slots free up as proofs finish, so hint at the key's average
+            // request spacing rather than the full window
+            let spacing = quota.spec.window_secs / quota.spec.rate_limit.max(1) as u64;
+            return Some(Err(Denied::Busy(spacing.max(1))));
+        }
+        quota.hits.push_back(now);
+        quota.active += 1;
+        Some(Ok(()))
+    }
+
+    /// Takes a slot for a job admitted before a restart, counting it against
+    /// the key's quota without denying it.
+    fn resume(&self, key: &KeyDigest) -> bool {
+        let mut keys = self.0.lock().expect("keys lock poisoned");
+        match keys.get_mut(key) {
+            Some(quota) => {
+                quota.hits.push_back(Instant::now());
+                quota.active += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn release(&self, key: &KeyDigest) {
+        let mut keys = self.0.lock().expect("keys lock poisoned");
+        if let Some(quota) = keys.get_mut(key) {
+            quota.active = quota.active.saturating_sub(1);
+        }
+    }
+}
+
+/// Fairing managing the key registry and adding `retry-after` headers to
+/// rate limited responses.
+pub struct Auth {
+    keys: Arc<Keys>,
+}
+
+impl Auth {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            bail!("api keys file {} not found", path.display());
+        }
+        let file: KeysFile = Figment::from(Toml::file_exact(path))
+            .extract()
+            .with_context(|| format!("invalid api keys file {}", path.display()))?;
+        if file.keys.is_empty() {
+            bail!("no api keys in {}", path.display());
+        }
+        Ok(Auth {
+            keys: Arc::new(Keys::new(file.keys)),
+        })
+    }
+}
+
+#[derive(Default)]
+struct RetryAfter(Option<u64>);
+
+#[rocket::async_trait]
+impl Fairing for Auth {
+    fn info(&self) -> Info {
+        Info {
+            name: "Authenticate API keys",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.manage(self.keys.clone()))
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if let RetryAfter(Some(secs)) = request.local_cache(RetryAfter::default) {
+            response.set_header(Header::new("retry-after", secs.to_string()));
+        }
+    }
+}
+
+/// Token of an `authorization: Bearer <token>` header, the auth scheme
+/// matched case-insensitively as per RFC 7235.
+fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then_some(token.trim())
+}
+
+/// Request guard holding one of the key's concurrency slots until dropped,
+/// can be moved into background jobs to hold the slot for their lifetime.
+/// Always succeeds if the `Auth` fairing is not attached.
+pub struct ApiKey {
+    slot: Option<(Arc<Keys>, KeyDigest)>,
+}
+
+impl ApiKey {
+    /// Hex digest identifying the key without revealing it, none if the
+    /// `Auth` fairing is not attached.
+    pub fn id(&self) -> Option<String> {
+        self.slot.as_ref().map(|(_, key)| const_hex::encode(key))
+    }
+
+    /// Retakes the slot of the key with given id for a job resumed after a
+    /// restart. Jobs of keys removed since hold no slot.
+    pub fn resume(keys: Option<&Arc<Keys>>, id: Option<&str>) -> Option<ApiKey> {
+        let (keys, id) = keys.zip(id)?;
+        let key = const_hex::decode_to_array::<&str, 32>(id).ok()?;
+        if !keys.resume(&key) {
+            log::warn!("🔑 resuming job of unknown api key");
+            return None;
+        }
+        Some(ApiKey {
+            slot: Some((keys.clone(), key)),
+        })
+    }
+}
+
+impl Drop for ApiKey {
+    fn drop(&mut self) {
+        if let Some((keys, key)) = &self.slot {
+            keys.release(key);
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let keys = match request.rocket().state::<Arc<Keys>>() {
+            Some(keys) => keys,
+            None => return Outcome::Success(ApiKey { slot: None }),
+        };
+        let key = match request
+            .headers()
+            .get_one("authorization")
+            .and_then(bearer_token)
+        {
+            Some(key) => digest(key),
+            None => return Outcome::Error((Status::Unauthorized, ())),
+        };
+        match keys.acquire(&key) {
+            None => Outcome::Error((Status::Unauthorized, ())),
+            Some(Ok(())) => Outcome::Success(ApiKey {
+                slot: Some((keys.clone(), key)),
+            }),
+            Some(Err(denied)) => {
+                let secs = match denied {
+                    Denied::RateLimited(secs) | Denied::Busy(secs) => secs,
+                };
+                request.local_cache(|| RetryAfter(Some(secs)));
+                Outcome::Error((Status::TooManyRequests, ()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spec(rate_limit: usize, max_concurrent: usize) -> KeySpec {
+        KeySpec {
+            key: "0a7c".to_string(),
+            rate_limit,
+            window_secs: 3600,
+            max_concurrent,
+        }
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer 0a7c"), Some("0a7c"));
+        assert_eq!(bearer_token("bearer  0a7c "), Some("0a7c"));
+        assert_eq!(bearer_token("BEARER 0a7c"), Some("0a7c"));
+        assert_eq!(bearer_token("Basic 0a7c"), None);
+        assert_eq!(bearer_token("Bearer"), None);
+    }
+
+    #[test]
+    fn test_acquire() {
+        let keys = Keys::new(vec![spec(2, 1)]);
+        assert!(keys.acquire(&digest("0a7d")).is_none());
+        assert!(matches!(keys.acquire(&digest("0a7c")), Some(Ok(()))));
+        assert!(matches!(
+            keys.acquire(&digest("0a7c")),
+            Some(Err(Denied::Busy(1800)))
+        ));
+        keys.release(&digest("0a7c"));
+        assert!(matches!(keys.acquire(&digest("0a7c")), Some(Ok(()))));
+        keys.release(&digest("0a7c"));
+        assert!(matches!(
+            keys.acquire(&digest("0a7c")),
+            Some(Err(Denied::RateLimited(secs))) if secs > 3500
+        ));
+    }
+
+    #[test]
+    fn test_resume() {
+        let keys = Arc::new(Keys::new(vec![spec(1, 1)]));
+        let id = const_hex::encode(digest("0a7c"));
+        let key = ApiKey::resume(Some(&keys), Some(&id)).unwrap();
+        assert_eq!(key.id(), Some(id));
+        assert!(matches!(
+            keys.acquire(&digest("0a7c")),
+            Some(Err(Denied::RateLimited(_)))
+        ));
+        drop(key);
+        assert_eq!(keys.0.lock().unwrap()[&digest("0a7c")].active, 0);
+        assert!(ApiKey::resume(Some(&keys), Some(&const_hex::encode(digest("0a7d")))).is_none());
+        assert!(ApiKey::resume(None, Some("0a7c")).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+//! CORS fairing with a configurable allowlist, from the `cors` settings'
+//! comma-separated `origins` (default `*`), `methods` and `headers`, and
+//! `credentials` which requires explicit origins.
+
+use crate::settings::CorsSettings;
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    http::{Header, Status},
+    request::Request,
+    Build, Response, Rocket,
+};
+
+pub const DEFAULT_METHODS: &str = "POST, GET, OPTIONS";
+pub const DEFAULT_HEADERS: &str = "content-type, authorization";
+const MAX_AGE: &str = "86400";
+
+pub struct Cors {
+    /// `None` allows any origin.
+    origins: Option<Vec<String>>,
+    methods: String,
+    headers: String,
+    credentials: bool,
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+impl Cors {
+    pub fn new(origins: &str, methods: &str, headers: &str, credentials: bool) -> Self {
+        let origins = split_list(origins);
+        let origins = if origins.is_empty() || origins.iter().any(|o| o == "*") {
+            None
+        } else {
+            Some(
+                origins
+                    .into_iter()
+                    .map(|o| o.trim_end_matches('/').to_lowercase())
+                    .collect(),
+            )
+        };
+        Cors {
+            credentials: credentials && origins.is_some(),
+            origins,
+            methods: split_list(methods).join(", "),
+            headers: split_list(headers).join(", "),
+        }
+    }
+
+    pub fn from_settings(cors: &CorsSettings) -> Self {
+        Self::new(
+            &cors.origins,
+            &cors.methods,
+            &cors.headers,
+            cors.credentials,
+        )
+    }
+
+    fn allow_origin(&self, origin: Option<&str>) -> Option<String> {
+        match (&self.origins, origin) {
+            (None, _) => Some("*".to_string()),
+            (Some(origins), Some(origin)) => origins
+                .contains(&origin.trim_end_matches('/').to_lowercase())
+                .then(|| origin.to_string()),
+            (Some(_), None) => None,
+        }
+    }
+}
+
+/// Answers any preflight request, the fairing adds the actual CORS headers.
+#[options("/<_..>")]
+fn preflight() -> Status {
+    Status::NoContent
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add CORS headers to responses",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.mount("/", routes![preflight]))
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if self.origins.is_some() {
+            response.adjoin_header(Header::new("vary", "origin"));
+        }
+        let origin = match self.allow_origin(request.headers().get_one("origin")) {
+            Some(origin) => origin,
+            None => return,
+        };
+        response.set_header(Header::new("access-control-allow-origin", origin));
+        response.set_header(Header::new(
+            "access-control-allow-methods",
+            self.methods.clone(),
+        ));
+        response.set_header(Header::new(
+            "access-control-allow-headers",
+            self.headers.clone(),
+        ));
+        response.set_header(Header::new("access-control-max-age", MAX_AGE));
+        if self.credentials {
+            response.set_header(Header::new("access-control-allow-credentials", "true"));
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Graceful shutdown: once SIGTERM or SIGINT arrive no new proofs are accepted
+//! and in-flight ones get all but `MERCY` of the configured `drain_secs` to
+//! finish, so the process exits within `drain_secs` plus a second. Jobs still
+//! unfinished by then are persisted to `jobs.json` in the configured
+//! `cache_dir` and resumed under the same job ids and api keys, anchored at
+//! the block they had already fetched, on next start.
+
+use crate::auth::{ApiKey, Keys};
+use crate::jobs::{self, PendingJob, JOBS};
+use crate::settings::Settings;
+use rocket::{
+    config::Shutdown,
+    fairing::{Fairing, Info, Kind},
+    serde::json::serde_json,
+    tokio::time::{sleep, Instant},
+    Orbit, Rocket,
+};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Seconds of the drain budget left for Rocket to close connections.
+pub const MERCY: u32 = 5;
+
+/// Set once shutdown has been triggered.
+pub static DRAINING: AtomicBool = AtomicBool::new(false);
+
+pub struct Drain {
+    jobs_file: PathBuf,
+    grace: u32,
+}
+
+impl Drain {
+    pub fn new(settings: &Settings) -> Self {
+        Drain {
+            jobs_file: settings.cache_dir.join("jobs.json"),
+            grace: settings.drain_secs - MERCY,
+        }
+    }
+
+    /// Shutdown config giving sync proof requests the same grace period as
+    /// jobs, Rocket starts its timers as shutdown fairings start running so
+    /// both share one deadline.
+    pub fn shutdown_config(&self) -> Shutdown {
+        Shutdown {
+            grace: self.grace,
+            mercy: MERCY,
+            ..Shutdown::default()
+        }
+    }
+
+    fn resume(&self, keys: Option<&Arc<Keys>>) {
+        let persisted = match std::fs::read(&self.jobs_file) {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        };
+        match serde_json::from_slice::<Vec<PendingJob>>(&persisted) {
+            Ok(persisted) => {
+                log::info!("♻️ resuming {} persisted jobs", persisted.len());
+                for job in persisted {
+                    let key = ApiKey::resume(keys, job.key_id.as_deref());
+                    JOBS.insert(job.job_id.clone(), job.params.clone(), job.key_id);
+                    jobs::run(job.job_id, job.params, job.block_number, key);
+                }
+            }
+            Err(err) => log::error!("invalid jobs file {}: {}", self.jobs_file.display(), err),
+        }
+        if let Err(err) = std::fs::remove_file(&self.jobs_file) {
+            log::error!(
+                "failed removing jobs file {}: {}",
+                self.jobs_file.display(),
+                err
+            );
+        }
+    }
+
+    fn persist(&self) {
+        let pending = JOBS.pending();
+        if pending.is_empty() {
+            return;
+        }
+        log::info!("💾 persisting {} unfinished jobs", pending.len());
+        let res = serde_json::to_vec(&pending)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(&self.jobs_file, json)?));
+        if let Err(err) = res {
+            log::error!(
+                "failed persisting jobs to {}: {}",
+                self.jobs_file.display(),
+                err
+            );
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Drain {
+    fn info(&self) -> Info {
+        Info {
+            name: "Drain and persist jobs on shutdown",
+            kind: Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        self.resume(rocket.state::<Arc<Keys>>());
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        DRAINING.store(true, Ordering::SeqCst);
+        log::info!("🛬 draining jobs");
+        let deadline = Instant::now() + Duration::from_secs(self.grace as u64);
+        while !JOBS.pending().is_empty() && Instant::now() < deadline {
+            sleep(Duration::from_secs(1)).await;
+        }
+        self.persist();
+    }
+}
@@ -0,0 +1,102 @@
+//! Proof request failures, telling client errors from the server's own so
+//! both get the right status, error message and metrics outcome.
+
+use rocket::{
+    http::Status,
+    serde::json::{json, Value},
+};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ProofError {
+    /// No RPC configured for the chain
+    UnknownChain(u64),
+    /// Malformed params or witness
+    Invalid(anyhow::Error),
+    /// Unusable `callback_url`, or no `callback_secret` to sign deliveries
+    Callback(anyhow::Error),
+    /// The Safe hasn't signed the message, or the storage proof is bogus
+    InvalidStorageProof(anyhow::Error),
+    /// Fetching inputs from the chain's RPC failed
+    Fetch(anyhow::Error),
+    /// The prover failed on inputs that checked out natively
+    Proving(anyhow::Error),
+}
+
+impl ProofError {
+    /// Metrics outcome, `invalid` for client errors, `failed` otherwise.
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            ProofError::UnknownChain(_)
+            | ProofError::Invalid(_)
+            | ProofError::Callback(_)
+            | ProofError::InvalidStorageProof(_) => "invalid",
+            ProofError::Fetch(_) | ProofError::Proving(_) => "failed",
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ProofError::UnknownChain(_) | ProofError::Invalid(_) => Status::BadRequest,
+            ProofError::Callback(_) => Status::UnprocessableEntity,
+            ProofError::InvalidStorageProof(_) | ProofError::Proving(_) => {
+                Status::InternalServerError
+            }
+            ProofError::Fetch(_) => Status::BadGateway,
+        }
+    }
+
+    /// Error message as exposed to clients, without internals.
+    pub fn message(&self) -> &'static str {
+        match self {
+            ProofError::UnknownChain(_) => "t(ツ)_/¯ invalid chain id",
+            ProofError::Invalid(_) => "t(ツ)_/¯ invalid request params",
+            ProofError::Callback(_) => "t(ツ)_/¯ invalid callback_url",
+            ProofError::InvalidStorageProof(_) => "t(ツ)_/¯ invalid storage proof",
+            ProofError::Fetch(_) => "t(ツ)_/¯ rpc request failed",
+            ProofError::Proving(_) => "t(ツ)_/¯ proving failed",
+        }
+    }
+
+    pub fn response(&self) -> (Status, Value) {
+        (self.status(), json!({ "error": self.message() }))
+    }
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::UnknownChain(chain_id) => write!(f, "invalid chain_id {}", chain_id),
+            ProofError::Invalid(err) => write!(f, "invalid: {:#}", err),
+            ProofError::Callback(err) => write!(f, "invalid callback: {:#}", err),
+            ProofError::InvalidStorageProof(err) => write!(f, "invalid storage proof: {:#}", err),
+            ProofError::Fetch(err) => write!(f, "fetch failed: {:#}", err),
+            ProofError::Proving(err) => write!(f, "proving failed: {:#}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_outcomes() {
+        let invalid = ProofError::InvalidStorageProof(anyhow::anyhow!("msg not signed"));
+        assert_eq!(invalid.outcome(), "invalid");
+        assert_eq!(invalid.status(), Status::InternalServerError);
+        let failed = ProofError::Proving(anyhow::anyhow!("execution failed"));
+        assert_eq!(failed.outcome(), "failed");
+        assert_eq!(failed.response().1["error"], "t(ツ)_/¯ proving failed");
+        assert_eq!(ProofError::UnknownChain(1).status(), Status::BadRequest);
+        let callback = ProofError::Callback(anyhow::anyhow!("private host"));
+        assert_eq!(callback.status(), Status::UnprocessableEntity);
+        assert_eq!(callback.outcome(), "invalid");
+        assert_eq!(
+            ProofError::Fetch(anyhow::anyhow!("timeout")).outcome(),
+            "failed"
+        );
+    }
+}
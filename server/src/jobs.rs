@@ -0,0 +1,304 @@
+//! Background proof jobs with progress events streamed via server-sent events.
+
+use crate::{
+    _proof, auth::ApiKey, check_callback, drain::DRAINING, logging::RequestId, metrics::Tracker,
+};
+use rocket::{
+    http::Status,
+    response::stream::{Event, EventStream},
+    serde::{
+        json::{json, Json, Value},
+        Deserialize, Serialize,
+    },
+    tokio::{
+        self,
+        sync::broadcast::{self, error::RecvError},
+    },
+};
+use sp1_safe_basics::{Progress, Sp1SafeParams};
+use std::collections::HashMap;
+use std::sync::{atomic::Ordering, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+/// How long finished jobs and their events are kept around.
+const JOB_TTL: Duration = Duration::from_secs(3600);
+
+struct Job {
+    params: Sp1SafeParams,
+    key_id: Option<String>,
+    events: Vec<Progress>,
+    tx: broadcast::Sender<Progress>,
+    finished: Option<Instant>,
+}
+
+/// What it takes to resume an unfinished job after a restart.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PendingJob {
+    pub job_id: String,
+    pub params: Sp1SafeParams,
+    /// Id of the api key the job counts against
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// Block the job's inputs were anchored at, if fetched already
+    #[serde(default)]
+    pub block_number: Option<u64>,
+}
+
+pub struct Jobs(Mutex<HashMap<String, Job>>);
+
+pub static JOBS: LazyLock<Jobs> = LazyLock::new(|| Jobs(Mutex::new(HashMap::new())));
+
+/// Drops jobs finished longer than `JOB_TTL` ago.
+fn prune(jobs: &mut HashMap<String, Job>) {
+    jobs.retain(|_, job| !matches!(job.finished, Some(t) if t.elapsed() >= JOB_TTL));
+}
+
+impl Jobs {
+    pub fn create(&self, params: Sp1SafeParams, key_id: Option<String>) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.insert(id.clone(), params, key_id);
+        id
+    }
+
+    /// Inserts a job under given id, used to resume persisted jobs.
+    pub fn insert(&self, id: String, params: Sp1SafeParams, key_id: Option<String>) {
+        let mut jobs = self.0.lock().expect("jobs lock poisoned");
+        prune(&mut jobs);
+        let (tx, _) = broadcast::channel(16);
+        jobs.insert(
+            id,
+            Job {
+                params,
+                key_id,
+                events: vec![Progress::Queued],
+                tx,
+                finished: None,
+            },
+        );
+    }
+
+    pub fn emit(&self, id: &str, progress: Progress) {
+        let mut jobs = self.0.lock().expect("jobs lock poisoned");
+        if let Some(job) = jobs.get_mut(id) {
+            if progress.is_final() {
+                job.finished = Some(Instant::now());
+            }
+            job.events.push(progress.clone());
+            // no receivers is fine, late subscribers replay job.events
+            let _ = job.tx.send(progress);
+        }
+    }
+
+    pub fn latest(&self, id: &str) -> Option<Progress> {
+        let mut jobs = self.0.lock().expect("jobs lock poisoned");
+        prune(&mut jobs);
+        jobs.get(id).and_then(|job| job.events.last().cloned())
+    }
+
+    /// Events after the first `seen` ones.
+    fn events_since(&self, id: &str, seen: usize) -> Option<Vec<Progress>> {
+        let jobs = self.0.lock().expect("jobs lock poisoned");
+        jobs.get(id)
+            .map(|job| job.events.get(seen..).unwrap_or_default().to_vec())
+    }
+
+    /// Unfinished jobs.
+    pub fn pending(&self) -> Vec<PendingJob> {
+        let jobs = self.0.lock().expect("jobs lock poisoned");
+        jobs.iter()
+            .filter(|(_, job)| job.finished.is_none())
+            .map(|(id, job)| PendingJob {
+                job_id: id.clone(),
+                params: job.params.clone(),
+                key_id: job.key_id.clone(),
+                block_number: job.events.iter().find_map(|progress| match progress {
+                    Progress::InputsFetched { block_number } => Some(*block_number),
+                    _ => None,
+                }),
+            })
+            .collect()
+    }
+
+    /// Snapshots past events and subscribes to future ones atomically.
+    fn subscribe(&self, id: &str) -> Option<(Vec<Progress>, broadcast::Receiver<Progress>)> {
+        let mut jobs = self.0.lock().expect("jobs lock poisoned");
+        prune(&mut jobs);
+        jobs.get(id)
+            .map(|job| (job.events.clone(), job.tx.subscribe()))
+    }
+}
+
+/// Proves in the background, anchored at given block or the latest one,
+/// holding given api key's concurrency slot until the job settles.
+pub fn run(id: String, params: Sp1SafeParams, block_number: Option<u64>, key: Option<ApiKey>) {
+    let span = tracing::info_span!("job", job_id = %id);
+    let task = async move {
+        let _key = key;
+        let mut tracker = Tracker::new(params.chain_id);
+        let emitter = id.clone();
+        let handle = tokio::spawn(
+            _proof(params, block_number, move |p| JOBS.emit(&emitter, p))
+                .instrument(tracing::Span::current()),
+        );
+        let progress = match handle.await {
+            Ok(Ok(result)) => {
+                tracker.set_outcome("ok");
+                Progress::Done { result }
+            }
+            Ok(Err(err)) => {
+                tracker.set_outcome(err.outcome());
+                tracing::error!(error = %err, "❌ job failed");
+                Progress::Failed {
+                    error: err.message().to_string(),
+                }
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "❌ job panicked");
+                Progress::Failed {
+                    error: "t(ツ)_/¯ internal error".to_string(),
+                }
+            }
+        };
+        JOBS.emit(&id, progress);
+    };
+    tokio::spawn(task.instrument(span));
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs",
+    request_body = Sp1SafeParams,
+    responses(
+        (status = 202, description = "Job accepted", body = Object,
+            example = json!({ "job_id": "3f1c0d9e-8a7b-4c6d-9e2f-1a2b3c4d5e6f" })),
+        (status = 401, description = "Invalid api key", body = Object),
+        (status = 422, description = "Unusable callback_url, or callbacks not configured",
+            body = Object, example = json!({ "error": "t(ツ)_/¯ invalid callback_url" })),
+        (status = 429, description = "Api key quota exceeded, see retry-after", body = Object),
+        (status = 503, description = "Shutting down", body = Object)
+    ),
+    security((), ("api_key" = []))
+)]
+#[post("/jobs", data = "<params>")]
+pub async fn create(
+    key: ApiKey,
+    request_id: RequestId,
+    params: Json<Sp1SafeParams>,
+) -> (Status, Value) {
+    if DRAINING.load(Ordering::SeqCst) {
+        return (
+            Status::ServiceUnavailable,
+            json!({ "error": "t(ツ)_/¯ shutting down" }),
+        );
+    }
+    let params = params.into_inner();
+    if let Err(err) = check_callback(&params).await {
+        tracing::error!(request_id = %request_id.0, error = %err, "❌ job rejected");
+        return err.response();
+    }
+    let id = JOBS.create(params.clone(), key.id());
+    tracing::info!(request_id = %request_id.0, job_id = %id, "📋 job created");
+    run(id.clone(), params, None, Some(key));
+    (Status::Accepted, json!({ "job_id": id }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    params(("id" = String, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "Latest job event", body = Progress),
+        (status = 404, description = "No such job")
+    )
+)]
+#[get("/jobs/<id>")]
+pub fn status(id: &str) -> Option<Json<Progress>> {
+    JOBS.latest(id).map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}/events",
+    params(("id" = String, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "Past and live job events, ending with done or failed",
+            content_type = "text/event-stream", body = Progress),
+        (status = 404, description = "No such job")
+    )
+)]
+#[get("/jobs/<id>/events")]
+pub fn events(id: &str) -> Option<EventStream![]> {
+    let (history, mut rx) = JOBS.subscribe(id)?;
+    let id = id.to_string();
+    Some(EventStream! {
+        let mut seen = 0;
+        let mut pending = history;
+        let mut done = false;
+        while !done {
+            for progress in pending.drain(..) {
+                seen += 1;
+                done = progress.is_final();
+                yield Event::json(&progress);
+            }
+            if done {
+                break;
+            }
+            // the channel only signals news, events are read from the job so
+            // lagging receivers catch up on everything they missed
+            match rx.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => match JOBS.events_since(&id, seen) {
+                    Some(events) => pending = events,
+                    None => done = true,
+                },
+                Err(RecvError::Closed) => done = true,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sp1_safe_basics::fixtures;
+
+    #[test]
+    fn test_events_since() {
+        let jobs = Jobs(Mutex::new(HashMap::new()));
+        let id = jobs.create(fixtures::params(), None);
+        jobs.emit(&id, Progress::Executed { cycles: 1 });
+        jobs.emit(&id, Progress::CoreProved);
+        let events = jobs.events_since(&id, 1).unwrap();
+        assert!(matches!(
+            events[..],
+            [Progress::Executed { .. }, Progress::CoreProved]
+        ));
+        assert!(jobs.events_since(&id, 9).unwrap().is_empty());
+        assert!(jobs.events_since("nope", 0).is_none());
+        assert!(matches!(jobs.latest(&id), Some(Progress::CoreProved)));
+    }
+
+    #[test]
+    fn test_pending() {
+        let jobs = Jobs(Mutex::new(HashMap::new()));
+        let id = jobs.create(fixtures::params(), Some("ab".repeat(32)));
+        jobs.emit(
+            &id,
+            Progress::InputsFetched {
+                block_number: fixtures::BLOCK_NUMBER,
+            },
+        );
+        let pending = jobs.pending();
+        assert_eq!(pending[0].key_id, Some("ab".repeat(32)));
+        assert_eq!(pending[0].block_number, Some(fixtures::BLOCK_NUMBER));
+
+        jobs.emit(
+            &id,
+            Progress::Failed {
+                error: "t(ツ)_/¯".to_string(),
+            },
+        );
+        assert!(jobs.pending().is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+//! Structured JSON logs, one object per line. Every request gets a
+//! correlation id, taken from its `x-request-id` header if well-formed,
+//! returned in the `x-request-id` response header and attached to all logs
+//! emitted while serving it. Safe addresses are only logged with `debug` set.
+//! In `privacy` mode every log line is scrubbed of hex strings as long as an
+//! address or longer, covering e.g. RPC errors quoting the Safe.
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    request::{FromRequest, Outcome, Request},
+    Data, Response,
+};
+use std::convert::Infallible;
+use std::io::{self, Write};
+use std::time::Instant;
+use tracing_subscriber::EnvFilter;
+
+pub const HEADER: &str = "x-request-id";
+
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+struct Started(Instant);
+
+/// Installs the JSON subscriber which also captures `log` records.
+pub fn init(level: &str, privacy: bool) {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::new(level))
+        .with_current_span(true)
+        .with_span_list(false)
+        .flatten_event(true)
+        .with_writer(move || Redacting {
+            enabled: privacy,
+            inner: io::stdout(),
+        })
+        .init();
+}
+
+/// Replaces hex runs of at least 40 digits, optionally `0x` prefixed.
+pub fn redact(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_hexdigit()) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let prefix = if rest.starts_with("0x") { 2 } else { 0 };
+        let len = rest[prefix..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(rest.len() - prefix);
+        if len >= 40 {
+            out.push_str("0x[redacted]");
+        } else {
+            out.push_str(&rest[..prefix + len]);
+        }
+        rest = &rest[prefix + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Stdout writer redacting if enabled, relies on each event being formatted
+/// into a single write.
+struct Redacting<W> {
+    enabled: bool,
+    inner: W,
+}
+
+impl<W: Write> Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.enabled {
+            return self.inner.write(buf);
+        }
+        let line = String::from_utf8_lossy(buf);
+        self.inner.write_all(redact(&line).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+impl RequestId {
+    fn of<'r>(req: &'r Request<'_>) -> &'r RequestId {
+        req.local_cache(|| {
+            let id = req
+                .headers()
+                .get_one(HEADER)
+                .filter(|id| valid(id))
+                .map(str::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            RequestId(id)
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(req).clone())
+    }
+}
+
+pub struct Correlation;
+
+#[rocket::async_trait]
+impl Fairing for Correlation {
+    fn info(&self) -> Info {
+        Info {
+            name: "Correlation ids and access logs",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| Started(Instant::now()));
+        RequestId::of(req);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let id = RequestId::of(req);
+        let started = req.local_cache(|| Started(Instant::now()));
+        res.set_raw_header(HEADER, id.0.clone());
+        tracing::info!(
+            request_id = %id.0,
+            method = %req.method(),
+            path = %req.uri().path(),
+            status = res.status().code,
+            duration_ms = started.0.elapsed().as_millis() as u64,
+            "📮 served"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_valid() {
+        assert!(valid("3f1c0d9e-8a7b-4c6d-9e2f-1a2b3c4d5e6f"));
+        assert!(!valid(""));
+        assert!(!valid("a\nb"));
+        assert!(!valid(&"a".repeat(65)));
+    }
+
+    #[test]
+    fn test_redact() {
+        let safe = "0x38Ba7f4278A1482FA0a7bC8B261a9A673336EDDc";
+        let hash = "a225aed0c0283cef82b24485b8b28fb756fc9ce83d25e5cf799d0c8aa20ce6b7";
+        assert_eq!(
+            redact(&format!(
+                r#"{{"error":"proof for {} at {} failed"}}"#,
+                safe, hash
+            )),
+            r#"{"error":"proof for 0x[redacted] at 0x[redacted] failed"}"#
+        );
+        assert_eq!(
+            redact(r#"{"block_number":33119702,"job_id":"3f1c0d9e-8a7b-4c6d"}"#),
+            r#"{"block_number":33119702,"job_id":"3f1c0d9e-8a7b-4c6d"}"#
+        );
+    }
+}
@@ -3,92 +3,430 @@
 #[macro_use]
 extern crate rocket;
 
-use anyhow::{bail, Result};
+mod auth;
+mod cors;
+mod drain;
+mod error;
+mod jobs;
+mod logging;
+mod metrics;
+mod openapi;
+mod sealed;
+mod settings;
+mod webhook;
+mod witness;
+
+use anyhow::{anyhow, Result};
+use auth::{ApiKey, Auth};
+use cors::Cors;
+use drain::{Drain, DRAINING};
+use error::ProofError;
+use logging::{Correlation, RequestId};
+use metrics::{Tracker, METRICS};
 use rocket::{
-    data::{Limits, ToByteUnit},
-    fairing::{Fairing, Info, Kind},
-    http::{Header, Method, Status},
+    fairing::AdHoc,
+    http::Status,
     request::Request,
     serde::json::{json, Json, Value},
-    Config, Response,
+    tokio::task,
+    Build, Rocket, Route,
+};
+use sealed::{SealedWitness, SEALING_KEY};
+use settings::{Settings, LOADED, SETTINGS};
+use sp1_safe_basics::{
+    compute_challenge, ChallengeParams, Inputs, Progress, PublicValues, Sp1SafeParams,
+    Sp1SafeResult,
 };
-use sp1_safe_basics::{Inputs, Sp1SafeParams, Sp1SafeResult};
-use sp1_safe_fetch::fetch_inputs;
-use sp1_sdk::{HashableKey, ProverClient, SP1ProvingKey, SP1Stdin, SP1VerifyingKey};
-use std::env;
-use std::net::Ipv4Addr;
-use std::sync::LazyLock;
+use sp1_safe_fetch::{fetch_inputs_at, header_block_number, verify_inputs};
+use sp1_safe_prove::{Proven, Prover, Stage};
+use sp1_sdk::{HashableKey, SP1Stdin};
+use std::sync::{atomic::Ordering, LazyLock};
+use tracing::Instrument;
+use witness::Witness;
 
 const ELF: &[u8] = include_bytes!("../../program/elf/riscv32im-succinct-zkvm-elf");
 
-struct Prover {
-    client: ProverClient,
-    pk: SP1ProvingKey,
-    vk: SP1VerifyingKey,
+static PROVER: LazyLock<Prover> = LazyLock::new(|| Prover::new(ELF, SETTINGS.prover_mode()));
+
+fn prove(stdin: SP1Stdin, progress: impl Fn(Progress)) -> Result<Proven> {
+    tracing::info!("🧮 executing");
+    let mut timer = None;
+    let proven = PROVER.prove(stdin, |stage| match stage {
+        Stage::Executed { cycles } => {
+            METRICS.cycles.observe(cycles as f64);
+            progress(Progress::Executed { cycles });
+            tracing::info!(cycles, "🎰 zk proving");
+            timer = Some(METRICS.proving_seconds.start_timer());
+        }
+        Stage::CoreProved => progress(Progress::CoreProved),
+        Stage::Compressed => progress(Progress::Compressed),
+        Stage::PlonkWrapped => progress(Progress::PlonkWrapped),
+    })?;
+    if let Some(timer) = timer {
+        let proving_ms = (timer.stop_and_record() * 1000.0) as u64;
+        tracing::info!(proving_ms, "🏁 proved");
+    }
+
+    Ok(proven)
 }
 
-static PROVER: LazyLock<Prover> = LazyLock::new(|| {
-    let client = ProverClient::new();
-    let (pk, vk) = client.setup(ELF);
-    Prover { client, pk, vk }
-});
+/// Rejects a `callback_url` the server can't sign deliveries for or mustn't
+/// deliver to.
+pub async fn check_callback(params: &Sp1SafeParams) -> Result<(), ProofError> {
+    let Some(url) = &params.callback_url else {
+        return Ok(());
+    };
+    if SETTINGS.callback_secret.is_none() {
+        return Err(ProofError::Callback(anyhow::anyhow!(
+            "no callback_secret configured"
+        )));
+    }
+    webhook::validate(url)
+        .await
+        .map(|_| ())
+        .map_err(ProofError::Callback)
+}
 
-async fn _proof(params: Json<Sp1SafeParams>) -> Result<Value> {
-    log::info!("🏈 incoming request");
-    let rpc = match params.chain_id {
-        100 => env::var("GNOSIS_RPC").unwrap_or("https://rpc.gnosis.gateway.fm".to_string()),
-        11155111 => env::var("SEPOLIA_RPC").unwrap_or("https://1rpc.io/sepolia".to_string()),
-        _ => bail!("invalid chain_id {}", params.chain_id),
+/// Proves given params against the Safe's state at given block, defaulting
+/// to the latest one.
+pub async fn _proof<F>(
+    params: Sp1SafeParams,
+    block_number: Option<u64>,
+    progress: F,
+) -> Result<Sp1SafeResult, ProofError>
+where
+    F: Fn(Progress) + Send + 'static,
+{
+    tracing::info!(
+        chain_id = params.chain_id,
+        safe_address = SETTINGS.debug.then_some(params.safe_address.as_str()),
+        "🏈 incoming request"
+    );
+    let rpc = SETTINGS
+        .rpc(params.chain_id)
+        .ok_or(ProofError::UnknownChain(params.chain_id))?;
+    check_callback(&params).await?;
+
+    let safe: [u8; 20] = const_hex::decode_to_array::<&str, 20>(&params.safe_address)
+        .map_err(|e| ProofError::Invalid(e.into()))?;
+    let msg_hash: [u8; 32] = const_hex::decode_to_array::<&str, 32>(&params.message_hash)
+        .map_err(|e| ProofError::Invalid(e.into()))?;
+
+    let timer = METRICS
+        .fetch_seconds
+        .with_label_values(&[&params.chain_id.to_string()])
+        .start_timer();
+    let (anchor, inputs) = fetch_inputs_at(
+        rpc,
+        safe.into(),
+        msg_hash.into(),
+        block_number,
+        SETTINGS.decoys,
+    )
+    .await
+    .map_err(ProofError::Fetch)?;
+    let fetch_ms = (timer.stop_and_record() * 1000.0) as u64;
+    tracing::info!(block_number = anchor, fetch_ms, "🕳️ fetched inputs");
+    // fail fast on unsigned messages rather than in the prover
+    verify_inputs(&inputs).map_err(ProofError::InvalidStorageProof)?;
+    progress(Progress::InputsFetched {
+        block_number: anchor,
+    });
+    let result = Sp1SafeResult {
+        // echo as requested
+        safe_address: params.safe_address,
+        message_hash: params.message_hash,
+        ..prove_inputs(params.chain_id, anchor, inputs, progress).await?
     };
+    if let (Some(url), Some(secret)) = (params.callback_url, &SETTINGS.callback_secret) {
+        webhook::spawn(url, secret.clone(), result.clone());
+    }
 
-    let safe: [u8; 20] = const_hex::decode_to_array::<&str, 20>(&params.safe_address)?;
-    let msg_hash: [u8; 32] = const_hex::decode_to_array::<&str, 32>(&params.message_hash)?;
+    Ok(result)
+}
 
-    log::info!("🕳️ fetching inputs");
-    let (anchor, inputs) = fetch_inputs(&rpc, safe.into(), msg_hash.into()).await?;
+async fn prove_inputs<F>(
+    chain_id: u64,
+    block_number: u64,
+    inputs: Inputs,
+    progress: F,
+) -> Result<Sp1SafeResult, ProofError>
+where
+    F: Fn(Progress) + Send + 'static,
+{
+    let safe_address = format!("0x{}", const_hex::encode(inputs.safe_address));
+    let message_hash = format!("0x{}", const_hex::encode(inputs.msg_hash));
     let mut stdin = SP1Stdin::new();
     stdin.write::<Inputs>(&inputs);
 
-    log::info!("🎰 zk proving");
-    let mut proofwpv = PROVER
-        .client
-        .prove_plonk(&PROVER.pk, stdin)
-        .expect("proving failed");
+    let Proven {
+        public_values,
+        proof,
+        ..
+    } = match task::spawn_blocking({
+        let span = tracing::Span::current();
+        move || span.in_scope(|| prove(stdin, progress))
+    })
+    .await
+    {
+        Ok(res) => res.map_err(ProofError::Proving)?,
+        // surface proving panics just like before moving off the executor
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    };
 
-    let blockhash = proofwpv.public_values.read::<[u8; 32]>();
-    let challenge = proofwpv.public_values.read::<[u8; 32]>();
-    let proofbin = bincode::serialize(&proofwpv.proof)?;
+    let PublicValues {
+        block_hash,
+        challenge,
+        ..
+    } = PublicValues::decode(public_values.as_slice())
+        .map_err(|e| ProofError::Proving(e.into()))?;
 
-    Ok(json!(Sp1SafeResult {
-        chain_id: params.chain_id,
-        safe_address: params.safe_address.to_owned(),
-        message_hash: params.message_hash.to_owned(),
-        block_number: anchor,
-        block_hash: format!("0x{}", const_hex::encode(blockhash)),
+    Ok(Sp1SafeResult {
+        chain_id,
+        safe_address,
+        message_hash,
+        block_number,
+        block_hash: format!("0x{}", const_hex::encode(block_hash)),
         challenge: format!("0x{}", const_hex::encode(challenge)),
-        proof: format!("0x{}", const_hex::encode(proofbin)),
-    }))
+        proof: format!("0x{}", const_hex::encode(proof)),
+    })
 }
 
+#[utoipa::path(
+    post,
+    path = "/proof",
+    request_body = Sp1SafeParams,
+    responses(
+        (status = 200, description = "Proof the Safe signed the message", body = Sp1SafeResult),
+        (status = 400, description = "Invalid params or chain id", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ invalid chain id" })),
+        (status = 401, description = "Invalid api key", body = Object),
+        (status = 422, description = "Unusable callback_url, or callbacks not configured",
+            body = Object, example = json!({ "error": "t(ツ)_/¯ invalid callback_url" })),
+        (status = 429, description = "Api key quota exceeded, see retry-after", body = Object),
+        (status = 500, description = "Message not signed by the Safe, or proving failed",
+            body = Object, example = json!({ "error": "t(ツ)_/¯ invalid storage proof" })),
+        (status = 502, description = "Fetching from the chain's RPC failed", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ rpc request failed" })),
+        (status = 503, description = "Shutting down", body = Object)
+    ),
+    security((), ("api_key" = []))
+)]
 #[post("/proof", data = "<params>")]
-async fn proof(params: Json<Sp1SafeParams>) -> (Status, Value) {
-    match _proof(params).await {
-        Ok(res) => (Status::Ok, res),
+async fn proof(_key: ApiKey, id: RequestId, params: Json<Sp1SafeParams>) -> (Status, Value) {
+    if DRAINING.load(Ordering::SeqCst) {
+        return (
+            Status::ServiceUnavailable,
+            json!({ "error": "t(ツ)_/¯ shutting down" }),
+        );
+    }
+    let mut tracker = Tracker::new(params.chain_id);
+    let span = tracing::info_span!("proof", request_id = %id.0);
+    match _proof(params.into_inner(), None, |_| ())
+        .instrument(span)
+        .await
+    {
+        Ok(res) => {
+            tracker.set_outcome("ok");
+            (Status::Ok, json!(res))
+        }
         Err(err) => {
-            log::error!("{}", err);
-            (
+            tracker.set_outcome(err.outcome());
+            tracing::error!(request_id = %id.0, error = %err, "❌ proof failed");
+            err.response()
+        }
+    }
+}
+
+/// Proves a client-supplied witness, if `sealed` neither logging the Safe
+/// address nor echoing it and the message hash in the response.
+async fn prove_witness(chain_id: u64, inputs: Inputs, sealed: bool) -> (Status, Value) {
+    if DRAINING.load(Ordering::SeqCst) {
+        return (
+            Status::ServiceUnavailable,
+            json!({ "error": "t(ツ)_/¯ shutting down" }),
+        );
+    }
+    let mut tracker = Tracker::new(chain_id);
+    if SETTINGS.rpc(chain_id).is_none() {
+        let err = ProofError::UnknownChain(chain_id);
+        tracker.set_outcome(err.outcome());
+        tracing::error!(error = %err, "❌ invalid witness");
+        return err.response();
+    }
+    tracing::info!(
+        chain_id,
+        safe_address = (SETTINGS.debug && !sealed)
+            .then(|| format!("0x{}", const_hex::encode(inputs.safe_address))),
+        "🎒 incoming witness"
+    );
+    let verified = verify_inputs(&inputs).and_then(|_| header_block_number(&inputs.header_rlp));
+    let block_number = match verified {
+        Ok(block_number) => block_number,
+        Err(err) => {
+            tracker.set_outcome("invalid");
+            tracing::error!(error = %err, "❌ invalid witness");
+            return (
                 Status::BadRequest,
                 json!({
-                    "error": "t(ツ)_/¯ invalid chain id"
+                    "error": "t(ツ)_/¯ invalid witness"
                 }),
+            );
+        }
+    };
+    match prove_inputs(chain_id, block_number, inputs, |_| ()).await {
+        Ok(res) => {
+            tracker.set_outcome("ok");
+            let mut res = json!(res);
+            if sealed {
+                // echoing these in cleartext would undo the sealing
+                if let Some(res) = res.as_object_mut() {
+                    res.remove("safe_address");
+                    res.remove("message_hash");
+                }
+            }
+            (Status::Ok, res)
+        }
+        Err(err) => {
+            tracker.set_outcome(err.outcome());
+            tracing::error!(error = %err, "❌ proof failed");
+            err.response()
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/witness",
+    params(("chain_id" = u64, Query, description = "Chain the witness was fetched from")),
+    request_body(content = String, content_type = "application/octet-stream",
+        description = "bincode-encoded Inputs, or JSON with content-type application/json"),
+    responses(
+        (status = 200, description = "Proof the Safe signed the message", body = Sp1SafeResult),
+        (status = 400, description = "Invalid witness or unconfigured chain id", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ invalid witness" })),
+        (status = 401, description = "Invalid api key", body = Object),
+        (status = 413, description = "Witness exceeds the witness limit", body = Object),
+        (status = 429, description = "Api key quota exceeded, see retry-after", body = Object),
+        (status = 500, description = "Proving failed", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ proving failed" })),
+        (status = 503, description = "Shutting down", body = Object)
+    ),
+    security((), ("api_key" = []))
+)]
+#[post("/witness?<chain_id>", data = "<witness>")]
+async fn plain_witness(
+    _key: ApiKey,
+    id: RequestId,
+    chain_id: u64,
+    witness: Witness,
+) -> (Status, Value) {
+    prove_witness(chain_id, witness.0, false)
+        .instrument(tracing::info_span!("witness", request_id = %id.0))
+        .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/witness/sealed",
+    params(("chain_id" = u64, Query, description = "Chain the witness was fetched from")),
+    request_body(content = String, content_type = "application/octet-stream",
+        description = "bincode-encoded Inputs sealed to the key from GET /witness/key"),
+    responses(
+        (status = 200, description = "Proof the Safe signed the message, a Sp1SafeResult \
+            without safe_address and message_hash", body = Object),
+        (status = 400, description = "Invalid witness or unconfigured chain id", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ invalid witness" })),
+        (status = 401, description = "Invalid api key", body = Object),
+        (status = 413, description = "Witness exceeds the witness limit", body = Object),
+        (status = 429, description = "Api key quota exceeded, see retry-after", body = Object),
+        (status = 500, description = "Proving failed", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ proving failed" })),
+        (status = 503, description = "Shutting down", body = Object)
+    ),
+    security((), ("api_key" = []))
+)]
+#[post("/witness/sealed?<chain_id>", data = "<witness>")]
+async fn sealed_witness(
+    _key: ApiKey,
+    id: RequestId,
+    chain_id: u64,
+    witness: SealedWitness,
+) -> (Status, Value) {
+    prove_witness(chain_id, witness.0, true)
+        .instrument(tracing::info_span!("witness", request_id = %id.0))
+        .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/witness/key",
+    responses((status = 200, description = "Key to seal witnesses to", body = Object,
+        example = json!({
+            "kem": "x25519-hkdf-sha256",
+            "aead": "chacha20poly1305",
+            "public_key": "0x8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+        })))
+)]
+#[get("/witness/key")]
+async fn witness_key() -> (Status, Value) {
+    (
+        Status::Ok,
+        json!({
+            "kem": sealed::KEM,
+            "aead": sealed::AEAD,
+            "public_key": format!("0x{}", const_hex::encode(SEALING_KEY.public.as_bytes())),
+        }),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/challenge",
+    request_body = ChallengeParams,
+    responses(
+        (status = 200, description = "Challenge a proof for the Safe and message commits to",
+            body = Object,
+            example = json!({
+                "challenge": "0x110bf0d0ea1251f73ef2ce30e6aec8fb2423e7c5d6563104789100168e252811"
+            })),
+        (status = 400, description = "Invalid Safe address or message hash", body = Object,
+            example = json!({ "error": "t(ツ)_/¯ invalid request params" }))
+    )
+)]
+#[post("/challenge", data = "<params>")]
+async fn challenge(params: Json<ChallengeParams>) -> (Status, Value) {
+    let safe_address = const_hex::decode_to_array::<&str, 20>(&params.safe_address);
+    let msg_hash = const_hex::decode_to_array::<&str, 32>(&params.message_hash);
+    match (safe_address, msg_hash) {
+        (Ok(safe_address), Ok(msg_hash)) => {
+            let challenge = compute_challenge(safe_address, msg_hash);
+            (
+                Status::Ok,
+                json!({ "challenge": format!("0x{}", const_hex::encode(challenge)) }),
             )
         }
+        _ => (
+            Status::BadRequest,
+            json!({
+                "error": "t(ツ)_/¯ invalid request params"
+            }),
+        ),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/status",
+    responses((status = 200, description = "Server status", body = Object,
+        example = json!({ "status": "ok", "prover_mode": "plonk" })))
+)]
 #[get("/status")]
 async fn status() -> (Status, Value) {
-    (Status::Ok, json!({ "status": "ok" }))
+    (
+        Status::Ok,
+        json!({ "status": "ok", "prover_mode": PROVER.mode.to_string() }),
+    )
 }
 
 #[catch(400)]
@@ -98,6 +436,27 @@ fn not_found(_: &Request) -> Value {
     })
 }
 
+#[catch(401)]
+fn unauthorized(_: &Request) -> Value {
+    json!({
+        "error": "t(ツ)_/¯ invalid api key"
+    })
+}
+
+#[catch(413)]
+fn payload_too_large(_: &Request) -> Value {
+    json!({
+        "error": "t(ツ)_/¯ payload too large"
+    })
+}
+
+#[catch(429)]
+fn too_many_requests(_: &Request) -> Value {
+    json!({
+        "error": "t(ツ)_/¯ too many requests"
+    })
+}
+
 #[catch(500)]
 fn internal_server_error(_: &Request) -> Value {
     json!({
@@ -105,52 +464,87 @@ fn internal_server_error(_: &Request) -> Value {
     })
 }
 
-pub struct CORS;
+fn routes() -> Vec<Route> {
+    routes![
+        proof,
+        plain_witness,
+        sealed_witness,
+        witness_key,
+        challenge,
+        status,
+        metrics::metrics,
+        jobs::create,
+        jobs::status,
+        jobs::events,
+        openapi::openapi
+    ]
+}
 
-#[rocket::async_trait]
-impl Fairing for CORS {
-    fn info(&self) -> Info {
-        Info {
-            name: "Add CORS headers to responses",
-            kind: Kind::Response,
-        }
-    }
+fn build(settings: &Settings) -> Result<Rocket<Build>> {
+    logging::init(&settings.log_level, settings.privacy);
+    let drain = Drain::new(settings);
+    let config = settings.rocket_config(drain.shutdown_config());
+
+    log::info!("vkey hash 0x{}", const_hex::encode(PROVER.vk.hash_bytes()));
+    log::info!("prover mode {}", PROVER.mode);
+    log::info!(
+        "witness sealing key 0x{}",
+        const_hex::encode(SEALING_KEY.public.as_bytes())
+    );
 
-    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("access-control-allow-origin", "*"));
-        response.set_header(Header::new(
-            "access-control-allow-methods",
-            "POST, GET, OPTIONS",
-        ));
-        response.set_header(Header::new("access-control-allow-headers", "*"));
-        response.set_status(Status {
-            code: if request.method() == Method::Options {
-                200
-            } else {
-                response.status().code
-            },
-        });
+    let rocket = rocket::custom(&config)
+        .attach(Correlation)
+        .attach(Cors::from_settings(&settings.cors))
+        .attach(drain)
+        .register(
+            "/",
+            catchers![
+                internal_server_error,
+                not_found,
+                unauthorized,
+                payload_too_large,
+                too_many_requests
+            ],
+        )
+        .mount("/", routes());
+
+    match &settings.api_keys_file {
+        Some(path) => Ok(rocket.attach(Auth::from_file(path)?)),
+        None => {
+            log::warn!("🔓 api key authentication disabled");
+            Ok(rocket)
+        }
     }
 }
 
 #[launch]
 fn rocket() -> _ {
-    std::env::set_var("RUST_LOG", "info");
-    sp1_sdk::utils::setup_logger();
-    let config = Config {
-        port: std::env::var("PORT")
-            .map(|p| p.parse::<u16>().expect("invalid port"))
-            .unwrap_or(4190),
-        address: Ipv4Addr::new(0, 0, 0, 0).into(),
-        ip_header: None,
-        limits: Limits::default().limit("json", 256.bytes()),
-        ..Config::release_default()
-    };
+    match LOADED
+        .as_ref()
+        .map_err(|err| anyhow!("{:#}", err))
+        .and_then(build)
+    {
+        Ok(rocket) => rocket,
+        // fail ignite naming the invalid setting
+        Err(err) => rocket::build().attach(AdHoc::try_on_ignite("Settings", |rocket| async move {
+            log::error!("❌ invalid settings: {:#}", err);
+            Err(rocket)
+        })),
+    }
+}
 
-    log::info!("vkey hash 0x{}", const_hex::encode(&PROVER.vk.hash_bytes()));
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sp1_safe_basics::fixtures;
 
-    rocket::custom(&config)
-        .attach(CORS)
-        .register("/", catchers![internal_server_error, not_found])
-        .mount("/", routes![proof, status])
+    #[rocket::async_test]
+    async fn test_prove_witness_draining() {
+        DRAINING.store(true, Ordering::SeqCst);
+        let plain = prove_witness(fixtures::CHAIN_ID, fixtures::inputs(), false).await;
+        let sealed = prove_witness(fixtures::CHAIN_ID, fixtures::inputs(), true).await;
+        DRAINING.store(false, Ordering::SeqCst);
+        assert_eq!(plain.0, Status::ServiceUnavailable);
+        assert_eq!(sealed.0, Status::ServiceUnavailable);
+    }
 }
@@ -0,0 +1,125 @@
+//! Prometheus metrics exposed at `GET /metrics`. There is no cache hit rate
+//! as the server caches neither inputs nor proofs, every request fetches
+//! fresh state and proves.
+
+use crate::settings::SETTINGS;
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use rocket::http::ContentType;
+use std::sync::LazyLock;
+
+pub struct Metrics {
+    registry: Registry,
+    pub requests: IntCounterVec,
+    pub fetch_seconds: HistogramVec,
+    pub proving_seconds: Histogram,
+    pub cycles: Histogram,
+    pub queue_depth: IntGauge,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new_custom(Some("sp1_safe".to_string()), None)
+        .expect("metrics registry init failed");
+    let requests = IntCounterVec::new(
+        Opts::new(
+            "proof_requests_total",
+            "Proof requests by outcome and chain",
+        ),
+        &["outcome", "chain_id"],
+    )
+    .expect("metric init failed");
+    let fetch_seconds = HistogramVec::new(
+        HistogramOpts::new("fetch_seconds", "RPC fetch latency of proof inputs"),
+        &["chain_id"],
+    )
+    .expect("metric init failed");
+    let proving_seconds = Histogram::with_opts(
+        HistogramOpts::new(
+            "proving_seconds",
+            "Proving duration in the configured prover mode",
+        )
+        // 0.1s to ~55min, mock proofs take well under a second
+        .buckets(exponential_buckets(0.1, 2., 16).expect("invalid buckets")),
+    )
+    .expect("metric init failed");
+    let cycles = Histogram::with_opts(
+        HistogramOpts::new("cycles", "SP1 execution cycle counts")
+            .buckets(exponential_buckets(1e6, 2., 10).expect("invalid buckets")),
+    )
+    .expect("metric init failed");
+    let queue_depth =
+        IntGauge::new("queue_depth", "Proof requests in flight").expect("metric init failed");
+
+    registry
+        .register(Box::new(requests.clone()))
+        .and_then(|_| registry.register(Box::new(fetch_seconds.clone())))
+        .and_then(|_| registry.register(Box::new(proving_seconds.clone())))
+        .and_then(|_| registry.register(Box::new(cycles.clone())))
+        .and_then(|_| registry.register(Box::new(queue_depth.clone())))
+        .expect("metric registration failed");
+
+    Metrics {
+        registry,
+        requests,
+        fetch_seconds,
+        proving_seconds,
+        cycles,
+        queue_depth,
+    }
+});
+
+/// Tracks a proof request in the queue depth gauge and counts its outcome
+/// once dropped: `ok`, `invalid` for client errors or `failed` for RPC and
+/// prover failures, the default which also covers panics. Chains the server
+/// isn't configured for are all labeled `unknown`.
+pub struct Tracker {
+    chain_id: String,
+    outcome: &'static str,
+}
+
+impl Tracker {
+    pub fn new(chain_id: u64) -> Self {
+        METRICS.queue_depth.inc();
+        Tracker {
+            chain_id: match SETTINGS.rpc(chain_id) {
+                Some(_) => chain_id.to_string(),
+                None => "unknown".to_string(),
+            },
+            outcome: "failed",
+        }
+    }
+
+    pub fn set_outcome(&mut self, outcome: &'static str) {
+        self.outcome = outcome;
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        METRICS.queue_depth.dec();
+        METRICS
+            .requests
+            .with_label_values(&[self.outcome, &self.chain_id])
+            .inc();
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses((status = 200, description = "Prometheus metrics", body = String,
+        content_type = "text/plain; version=0.0.4"))
+)]
+#[get("/metrics")]
+pub fn metrics() -> (ContentType, String) {
+    let mut buf = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buf)
+        .expect("metrics encoding failed");
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        String::from_utf8(buf).expect("metrics not utf8"),
+    )
+}
@@ -0,0 +1,103 @@
+//! OpenAPI 3 document served at `/openapi.json`, with schemas derived from the
+//! request and response types and operations from the annotated handlers.
+
+use crate::{jobs, metrics};
+use rocket::serde::json::Json;
+use sp1_safe_basics::{ChallengeParams, Progress, Sp1SafeParams, Sp1SafeResult};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "sp1-safe", description = "Prove a Safe multisig over a message in zk"),
+    paths(
+        crate::proof,
+        crate::plain_witness,
+        crate::sealed_witness,
+        crate::witness_key,
+        crate::challenge,
+        crate::status,
+        metrics::metrics,
+        jobs::create,
+        jobs::status,
+        jobs::events,
+        openapi
+    ),
+    components(schemas(Sp1SafeParams, ChallengeParams, Sp1SafeResult, Progress)),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/// Documents the optional `authorization: Bearer <key>` header.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "api_key",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    responses((status = 200, description = "This document", body = Object))
+)]
+#[get("/openapi.json")]
+pub fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rocket::serde::json::serde_json;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_in_sync() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let documented = doc["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| format!("{} {}", method, path))
+            })
+            .collect::<BTreeSet<_>>();
+        let mounted = crate::routes()
+            .iter()
+            .map(|route| {
+                let path = route.uri.path().replace('<', "{").replace('>', "}");
+                format!("{} {}", route.method.as_str().to_lowercase(), path)
+            })
+            .collect::<BTreeSet<_>>();
+        assert_eq!(documented, mounted);
+    }
+
+    #[test]
+    fn test_examples_deserialize() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &doc["components"]["schemas"];
+        let example = |schema: &str| {
+            let props = schemas[schema]["properties"].as_object().unwrap();
+            props
+                .iter()
+                .map(|(k, v)| (k.clone(), v["example"].clone()))
+                .collect::<serde_json::Map<_, _>>()
+        };
+        serde_json::from_value::<Sp1SafeParams>(example("Sp1SafeParams").into()).unwrap();
+        serde_json::from_value::<ChallengeParams>(example("ChallengeParams").into()).unwrap();
+        serde_json::from_value::<Sp1SafeResult>(example("Sp1SafeResult").into()).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+//! Witnesses sealed to the server's X25519 public key, see
+//! `sp1_safe_basics::sealed` for the scheme and why senders should pin that
+//! key out of band. The decrypted bytes are zeroized once decoded, the
+//! decoded `Inputs` and the prover's copies of it are not, but neither
+//! plaintext nor anything derived from it is logged or echoed back.
+
+use crate::settings::SETTINGS;
+use crate::witness::read_witness_bytes;
+use anyhow::{anyhow, Error};
+use rocket::{
+    data::{self, Data, FromData},
+    http::Status,
+    request::Request,
+};
+pub use sp1_safe_basics::sealed::{SealingKey, AEAD, KEM};
+use sp1_safe_basics::{sealed::StaticSecret, Inputs};
+use std::sync::LazyLock;
+
+/// From setting `witness_secret_key` or generated once per process which
+/// invalidates witnesses sealed to a previous key on restarts.
+pub static SEALING_KEY: LazyLock<SealingKey> = LazyLock::new(|| {
+    let secret = match &SETTINGS.witness_secret_key {
+        Some(hex) => StaticSecret::from(
+            const_hex::decode_to_array::<&str, 32>(hex).expect("validated witness_secret_key"),
+        ),
+        None => {
+            log::warn!("🔑 witness_secret_key not set, using an ephemeral sealing key");
+            StaticSecret::random()
+        }
+    };
+    SealingKey::new(secret)
+});
+
+pub struct SealedWitness(pub Inputs);
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for SealedWitness {
+    type Error = Error;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let sealed = match read_witness_bytes(req, data).await {
+            Ok(sealed) => sealed,
+            Err(err) => return data::Outcome::Error(err),
+        };
+        // don't echo deserialization errors as those may quote plaintext
+        let inputs = SEALING_KEY
+            .open(&sealed)
+            .map_err(Error::from)
+            .and_then(|plaintext| {
+                bincode::deserialize::<Inputs>(&plaintext)
+                    .map_err(|_| anyhow!("invalid sealed witness"))
+            });
+        match inputs {
+            Ok(inputs) => data::Outcome::Success(SealedWitness(inputs)),
+            Err(err) => data::Outcome::Error((Status::BadRequest, err)),
+        }
+    }
+}
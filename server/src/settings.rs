@@ -0,0 +1,396 @@
+//! Server settings read from a TOML file, `sp1-safe.toml` or the one named by
+//! env var `SP1_SAFE_CONFIG`, overridable per key via `SP1_SAFE_`-prefixed env
+//! vars with `__` separating nested keys, e.g. `SP1_SAFE_LIMITS__JSON=1KiB` or
+//! `SP1_SAFE_CHAINS__100=https://rpc.gnosischain.com`. The former env vars
+//! `PORT`, `PROVER_MODE`, `GNOSIS_RPC`, `SEPOLIA_RPC`, `API_KEYS_FILE`,
+//! `CORS_ORIGINS`, `CORS_METHODS`, `CORS_HEADERS`, `CORS_CREDENTIALS`,
+//! `CALLBACK_SECRET`, `WITNESS_SECRET_KEY` and `DRAIN_SECS` are still honored.
+
+use crate::auth::Auth;
+use crate::cors::{DEFAULT_HEADERS, DEFAULT_METHODS};
+use crate::drain::MERCY;
+use anyhow::{anyhow, bail, Context, Result};
+use rocket::{
+    config::{Shutdown, TlsConfig},
+    data::{ByteUnit, Limits, ToByteUnit},
+    figment::{
+        providers::{Env, Format, Serialized, Toml},
+        Figment,
+    },
+    serde::{Deserialize, Serialize},
+    Config,
+};
+use sp1_safe_prove::ProverMode;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TlsSettings {
+    /// PEM certificate chain
+    pub certs: PathBuf,
+    /// PEM private key
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LimitSettings {
+    pub json: ByteUnit,
+    pub witness: ByteUnit,
+}
+
+/// Comma-separated allowlists, `*` allowing any origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CorsSettings {
+    pub origins: String,
+    pub methods: String,
+    pub headers: String,
+    /// Only allowed with explicit origins
+    pub credentials: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Settings {
+    pub address: IpAddr,
+    pub port: u16,
+    pub tls: Option<TlsSettings>,
+    pub limits: LimitSettings,
+    pub log_level: String,
+    /// Logs Safe addresses which are otherwise left out
+    pub debug: bool,
+    /// Redacts anything resembling an address or hash from all logs
+    pub privacy: bool,
+    /// Number of other contracts whose proofs are fetched alongside the
+    /// Safe's, which doesn't hide the Safe from the RPC provider
+    pub decoys: usize,
+    pub prover_mode: String,
+    /// Chain id to RPC url
+    pub chains: BTreeMap<String, String>,
+    /// Directory for state kept across restarts, i.e. jobs drained on shutdown
+    pub cache_dir: PathBuf,
+    /// HMAC key signing webhook deliveries, requests with a `callback_url`
+    /// are rejected without one
+    pub callback_secret: Option<String>,
+    /// Seconds from shutdown until the process exits, in-flight proofs get
+    /// all but a few of them to finish
+    pub drain_secs: u32,
+    /// TOML file of bearer api keys and their quotas, auth is off without one
+    pub api_keys_file: Option<PathBuf>,
+    pub cors: CorsSettings,
+    /// 32 hex bytes of X25519 secret key witnesses get sealed to, generated
+    /// per process if unset
+    pub witness_secret_key: Option<String>,
+}
+
+/// Like a derived `Debug` but redacting the secrets.
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("Settings")
+            .field("address", &self.address)
+            .field("port", &self.port)
+            .field("tls", &self.tls)
+            .field("limits", &self.limits)
+            .field("log_level", &self.log_level)
+            .field("debug", &self.debug)
+            .field("privacy", &self.privacy)
+            .field("decoys", &self.decoys)
+            .field("prover_mode", &self.prover_mode)
+            .field("chains", &self.chains)
+            .field("cache_dir", &self.cache_dir)
+            .field("callback_secret", &redacted(&self.callback_secret))
+            .field("drain_secs", &self.drain_secs)
+            .field("api_keys_file", &self.api_keys_file)
+            .field("cors", &self.cors)
+            .field("witness_secret_key", &redacted(&self.witness_secret_key))
+            .finish()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            address: Ipv4Addr::new(0, 0, 0, 0).into(),
+            port: 4190,
+            tls: None,
+            limits: LimitSettings {
+                json: 256.bytes(),
+                witness: 128.kibibytes(),
+            },
+            log_level: "info".to_string(),
+            debug: false,
+            privacy: false,
+            decoys: 0,
+            prover_mode: ProverMode::default().to_string(),
+            chains: BTreeMap::from([
+                (
+                    "100".to_string(),
+                    "https://rpc.gnosis.gateway.fm".to_string(),
+                ),
+                (
+                    "11155111".to_string(),
+                    "https://1rpc.io/sepolia".to_string(),
+                ),
+            ]),
+            cache_dir: PathBuf::from("."),
+            callback_secret: None,
+            drain_secs: 300,
+            api_keys_file: None,
+            cors: CorsSettings {
+                origins: "*".to_string(),
+                methods: DEFAULT_METHODS.to_string(),
+                headers: DEFAULT_HEADERS.to_string(),
+                credentials: false,
+            },
+            witness_secret_key: None,
+        }
+    }
+}
+
+/// Loaded and validated once, invalid settings fail Rocket's ignite.
+pub static LOADED: LazyLock<Result<Settings>> = LazyLock::new(Settings::load);
+
+/// The settings, only dereferenced once ignite got past invalid ones.
+pub static SETTINGS: LazyLock<&Settings> =
+    LazyLock::new(|| LOADED.as_ref().expect("settings validated before ignite"));
+
+impl Settings {
+    pub fn load() -> Result<Self> {
+        let path = match std::env::var("SP1_SAFE_CONFIG") {
+            Ok(path) if !Path::new(&path).is_file() => bail!("config file {} not found", path),
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from("sp1-safe.toml")).filter(|path| path.is_file()),
+        };
+        let mut figment = Figment::from(Serialized::defaults(Settings::default()));
+        if let Some(path) = path {
+            figment = figment.merge(Toml::file_exact(path));
+        }
+        let settings: Settings = figment
+            .merge(Env::raw().only(&["PORT", "PROVER_MODE"]))
+            .merge(
+                Env::raw()
+                    .only(&["GNOSIS_RPC"])
+                    .map(|_| "chains.100".into()),
+            )
+            .merge(
+                Env::raw()
+                    .only(&["SEPOLIA_RPC"])
+                    .map(|_| "chains.11155111".into()),
+            )
+            .merge(Env::raw().only(&["CALLBACK_SECRET"]))
+            .merge(Env::raw().only(&["API_KEYS_FILE", "WITNESS_SECRET_KEY", "DRAIN_SECS"]))
+            .merge(
+                Env::raw()
+                    .only(&[
+                        "CORS_ORIGINS",
+                        "CORS_METHODS",
+                        "CORS_HEADERS",
+                        "CORS_CREDENTIALS",
+                    ])
+                    .map(|key| {
+                        key.as_str()
+                            .to_lowercase()
+                            .replacen("cors_", "cors.", 1)
+                            .into()
+                    }),
+            )
+            .merge(Env::prefixed("SP1_SAFE_").ignore(&["CONFIG"]).split("__"))
+            .extract()?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(tls) = &self.tls {
+            for path in [&tls.certs, &tls.key] {
+                if !path.is_file() {
+                    bail!("tls file {} not found", path.display());
+                }
+            }
+        }
+        if self.limits.json.as_u64() == 0 || self.limits.witness.as_u64() == 0 {
+            bail!("limits must be nonzero");
+        }
+        self.log_level
+            .parse::<log::LevelFilter>()
+            .with_context(|| format!("invalid log_level {}", self.log_level))?;
+        if self.privacy && self.debug {
+            bail!("privacy and debug are mutually exclusive");
+        }
+        if self.decoys > 16 {
+            bail!("at most 16 decoys");
+        }
+        self.prover_mode.parse::<ProverMode>()?;
+        if self.chains.is_empty() {
+            bail!("no chains configured");
+        }
+        for (chain_id, rpc) in &self.chains {
+            chain_id
+                .parse::<u64>()
+                .with_context(|| format!("invalid chain id {}", chain_id))?;
+            if !rpc.starts_with("http://") && !rpc.starts_with("https://") {
+                bail!("invalid rpc url {} for chain {}", rpc, chain_id);
+            }
+        }
+        if self.callback_secret.as_ref().is_some_and(String::is_empty) {
+            bail!("empty callback_secret");
+        }
+        if let Some(path) = &self.api_keys_file {
+            Auth::from_file(path)?;
+        }
+        if self.cors.credentials && self.cors.origins.split(',').any(|o| o.trim() == "*") {
+            bail!("cors.credentials requires explicit cors.origins");
+        }
+        for origin in self.cors.origins.split(',').map(str::trim) {
+            if origin != "*" && !origin.starts_with("http://") && !origin.starts_with("https://") {
+                bail!("invalid cors origin {}", origin);
+            }
+        }
+        if let Some(key) = &self.witness_secret_key {
+            const_hex::decode_to_array::<&str, 32>(key)
+                .map_err(|_| anyhow!("witness_secret_key must be 32 hex bytes"))?;
+        }
+        if self.drain_secs <= MERCY {
+            bail!("drain_secs must exceed {}", MERCY);
+        }
+        std::fs::create_dir_all(&self.cache_dir)
+            .with_context(|| format!("unusable cache_dir {}", self.cache_dir.display()))?;
+        Ok(())
+    }
+
+    pub fn prover_mode(&self) -> ProverMode {
+        self.prover_mode.parse().expect("validated prover mode")
+    }
+
+    pub fn rpc(&self, chain_id: u64) -> Option<&str> {
+        self.chains.get(&chain_id.to_string()).map(String::as_str)
+    }
+
+    pub fn rocket_config(&self, shutdown: Shutdown) -> Config {
+        Config {
+            port: self.port,
+            address: self.address,
+            ip_header: None,
+            shutdown,
+            tls: self
+                .tls
+                .as_ref()
+                .map(|tls| TlsConfig::from_paths(&tls.certs, &tls.key)),
+            limits: Limits::default()
+                .limit("json", self.limits.json)
+                .limit("witness", self.limits.witness),
+            ..Config::release_default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_valid() {
+        let settings = Settings::default();
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.prover_mode(), ProverMode::Plonk);
+        assert_eq!(settings.rpc(100), Some("https://rpc.gnosis.gateway.fm"));
+        assert_eq!(settings.rpc(1), None);
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let settings = Settings {
+            callback_secret: Some("hmac-secret".to_string()),
+            witness_secret_key: Some(format!("0x{}", "ab".repeat(32))),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", settings);
+        assert!(!debug.contains("hmac-secret"));
+        assert!(!debug.contains(&"ab".repeat(32)));
+        assert!(debug.contains("callback_secret: Some(\"<redacted>\")"));
+        assert!(debug.contains("witness_secret_key: Some(\"<redacted>\")"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = [
+            Settings {
+                prover_mode: "fast".to_string(),
+                ..Default::default()
+            },
+            Settings {
+                privacy: true,
+                debug: true,
+                ..Default::default()
+            },
+            Settings {
+                log_level: "loud".to_string(),
+                ..Default::default()
+            },
+            Settings {
+                chains: BTreeMap::from([("gnosis".to_string(), "https://x".to_string())]),
+                ..Default::default()
+            },
+            Settings {
+                tls: Some(TlsSettings {
+                    certs: "missing.pem".into(),
+                    key: "missing.key".into(),
+                }),
+                ..Default::default()
+            },
+            Settings {
+                callback_secret: Some(String::new()),
+                ..Default::default()
+            },
+            Settings {
+                drain_secs: MERCY,
+                ..Default::default()
+            },
+            Settings {
+                api_keys_file: Some("missing.toml".into()),
+                ..Default::default()
+            },
+            Settings {
+                cors: CorsSettings {
+                    credentials: true,
+                    ..Settings::default().cors
+                },
+                ..Default::default()
+            },
+            Settings {
+                cors: CorsSettings {
+                    origins: "app.example.org".to_string(),
+                    ..Settings::default().cors
+                },
+                ..Default::default()
+            },
+            Settings {
+                witness_secret_key: Some("0xabcd".to_string()),
+                ..Default::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
+
+    #[test]
+    fn test_cors_valid() {
+        let settings = Settings {
+            cors: CorsSettings {
+                origins: "https://app.example.org, https://example.org".to_string(),
+                credentials: true,
+                ..Settings::default().cors
+            },
+            witness_secret_key: Some(format!("0x{}", "ab".repeat(32))),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+}
@@ -0,0 +1,293 @@
+//! Webhook callbacks POSTing a `Sp1SafeResult` to the request's `callback_url`
+//! once proven. Each delivery is signed with HMAC-SHA256 over
+//! `"{timestamp}.{body}"` keyed by setting `callback_secret`, receivers should
+//! recompute it and compare with the `x-sp1-safe-signature` header in constant
+//! time. Failed deliveries are retried with exponential backoff.
+//!
+//! Callback hosts must resolve to public addresses only, deliveries connect to
+//! the addresses checked and don't follow redirects, so callers can't point
+//! the server at loopback, private networks or cloud metadata endpoints.
+
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use reqwest::{redirect::Policy, Url};
+use rocket::{
+    serde::json::serde_json,
+    tokio::{self, net::lookup_host, time::sleep},
+};
+use sha2::Sha256;
+use sp1_safe_basics::Sp1SafeResult;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const SIGNATURE_HEADER: &str = "x-sp1-safe-signature";
+pub const TIMESTAMP_HEADER: &str = "x-sp1-safe-timestamp";
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+pub fn sign(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac takes keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", const_hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether given address is globally routable, i.e. not loopback, link-local,
+/// private, shared, documentation, multicast or otherwise reserved. IPv6
+/// addresses embedding an IPv4 one, i.e. IPv4-mapped, IPv4-compatible, NAT64
+/// and 6to4, are judged by that IPv4 address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // shared address space 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                // ietf protocol assignments 192.0.0.0/24
+                || (a == 192 && b == 0 && ip.octets()[2] == 0)
+                // benchmarking 198.18.0.0/15
+                || (a == 198 && (18..20).contains(&b))
+                // reserved 240.0.0.0/4
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(ip.into());
+            }
+            let [a, b, c, d, e, f, g, h] = ip.segments();
+            let embedded = |hi: u16, lo: u16| {
+                let [a, b] = hi.to_be_bytes();
+                let [c, d] = lo.to_be_bytes();
+                Ipv4Addr::new(a, b, c, d)
+            };
+            // ipv4-compatible ::a.b.c.d, sparing :: and ::1
+            if [a, b, c, d, e, f] == [0; 6] && !(ip.is_unspecified() || ip.is_loopback()) {
+                return is_public(embedded(g, h).into());
+            }
+            // nat64 64:ff9b::/96
+            if [a, b, c, d, e, f] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                return is_public(embedded(g, h).into());
+            }
+            // 6to4 2002::/16
+            if a == 0x2002 {
+                return is_public(embedded(b, c).into());
+            }
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // local-use nat64 64:ff9b:1::/48, translating to anything
+                || (a == 0x64 && b == 0xff9b && c == 1)
+                // unique local fc00::/7
+                || (a & 0xfe00) == 0xfc00
+                // link-local fe80::/10
+                || (a & 0xffc0) == 0xfe80
+                // documentation 2001:db8::/32
+                || (a == 0x2001 && b == 0xdb8))
+        }
+    }
+}
+
+/// Rejects callback urls that are not plain http(s) or whose host resolves
+/// to any non-public address, returning the addresses to deliver to.
+pub async fn validate(url: &str) -> Result<Vec<SocketAddr>> {
+    let parsed = match Url::parse(url) {
+        Ok(url) if url.scheme() == "https" || url.scheme() == "http" => url,
+        _ => bail!("invalid callback_url {}", url),
+    };
+    let host = parsed.host_str().context("callback_url without host")?;
+    let port = parsed
+        .port_or_known_default()
+        .context("callback_url without port")?;
+    let addrs = lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .with_context(|| format!("unresolvable callback_url host {}", host))?
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        bail!("unresolvable callback_url host {}", host);
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        bail!("callback_url host {} resolves to {}", host, addr.ip());
+    }
+    Ok(addrs)
+}
+
+/// POSTs given result to `url`, connecting to `addrs` if given rather than
+/// resolving the host again.
+pub async fn deliver(
+    url: &str,
+    addrs: &[SocketAddr],
+    secret: &[u8],
+    result: &Sp1SafeResult,
+    mut backoff: Duration,
+) -> Result<()> {
+    let body = serde_json::to_vec(result)?;
+    let mut client = reqwest::Client::builder().redirect(Policy::none());
+    if let Some(host) = Url::parse(url)?.host_str() {
+        if !addrs.is_empty() {
+            client = client.resolve_to_addrs(host, addrs);
+        }
+    }
+    let client = client.build()?;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let res = client
+            .post(url)
+            .header("content-type", "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, &body))
+            .body(body.clone())
+            .send()
+            .await;
+        match res {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => log::warn!("📮 callback attempt {} got {}", attempt, res.status()),
+            Err(err) => log::warn!("📮 callback attempt {} failed: {}", attempt, err),
+        }
+        if attempt < MAX_ATTEMPTS {
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    bail!("callback failed after {} attempts", MAX_ATTEMPTS)
+}
+
+/// Delivers in the background, validating the url again right before so the
+/// host can't be rebound to a private address after the request was accepted.
+pub fn spawn(url: String, secret: String, result: Sp1SafeResult) {
+    tokio::spawn(async move {
+        let delivered = match validate(&url).await {
+            Ok(addrs) => deliver(&url, &addrs, secret.as_bytes(), &result, INITIAL_BACKOFF).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = delivered {
+            log::error!("{}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sp1_safe_basics::fixtures::result;
+    use wiremock::{
+        http::HeaderName,
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// Mock receiver answering with given statuses in order.
+    async fn receiver(statuses: &[u16]) -> MockServer {
+        let server = MockServer::start().await;
+        for status in statuses {
+            Mock::given(method("POST"))
+                .and(path("/callback"))
+                .respond_with(ResponseTemplate::new(*status))
+                .up_to_n_times(1)
+                .with_priority(1)
+                .mount(&server)
+                .await;
+        }
+        server
+    }
+
+    #[rocket::async_test]
+    async fn test_deliver_signed() {
+        let server = receiver(&[200]).await;
+        let url = format!("{}/callback", server.uri());
+        let result = result();
+        deliver(&url, &[], b"secret", &result, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let req = &server.received_requests().await.unwrap()[0];
+        let header = |name: &'static str| {
+            req.headers
+                .get(&HeaderName::from(name))
+                .unwrap()
+                .last()
+                .as_str()
+        };
+        let timestamp = header(TIMESTAMP_HEADER).parse::<u64>().unwrap();
+        assert_eq!(
+            header(SIGNATURE_HEADER),
+            sign(b"secret", timestamp, &req.body)
+        );
+        let delivered: Sp1SafeResult = serde_json::from_slice(&req.body).unwrap();
+        assert_eq!(delivered.challenge, result.challenge);
+    }
+
+    #[rocket::async_test]
+    async fn test_deliver_retries() {
+        let server = receiver(&[500, 503, 200]).await;
+        let url = format!("{}/callback", server.uri());
+        deliver(&url, &[], b"secret", &result(), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[rocket::async_test]
+    async fn test_deliver_gives_up() {
+        let server = receiver(&[500; MAX_ATTEMPTS as usize]).await;
+        let url = format!("{}/callback", server.uri());
+        assert!(deliver(&url, &[], b"secret", &result(), Duration::ZERO)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in [
+            "1.1.1.1",
+            "2606:4700:4700::1111",
+            "::1.1.1.1",
+            "64:ff9b::101:101",
+            "2002:101:101::1",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b:1::1",
+            "2002:7f00:1::1",
+            "2002:c0a8:101::",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_validate() {
+        assert!(validate("https://1.1.1.1/hook").await.is_ok());
+        assert!(validate("http://169.254.169.254/latest/meta-data")
+            .await
+            .is_err());
+        assert!(validate("http://127.0.0.1:8000/hook").await.is_err());
+        assert!(validate("https://[::1]/hook").await.is_err());
+        assert!(validate("file:///etc/passwd").await.is_err());
+        assert!(validate("not a url").await.is_err());
+    }
+}
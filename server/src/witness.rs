@@ -0,0 +1,47 @@
+//! Client-supplied `Inputs` witnesses, either bincode (the zkVM's own encoding)
+//! or JSON if sent with `content-type: application/json`. Read up to the
+//! `witness` limit which is separate from and larger than the `json` one.
+
+use anyhow::{anyhow, Error};
+use rocket::{
+    data::{self, Data, FromData, ToByteUnit},
+    http::{ContentType, Status},
+    request::Request,
+    serde::json::serde_json,
+};
+use sp1_safe_basics::Inputs;
+
+pub struct Witness(pub Inputs);
+
+pub async fn read_witness_bytes(
+    req: &Request<'_>,
+    data: Data<'_>,
+) -> Result<Vec<u8>, (Status, Error)> {
+    let limit = req.limits().get("witness").unwrap_or(128.kibibytes());
+    match data.open(limit).into_bytes().await {
+        Ok(bytes) if bytes.is_complete() => Ok(bytes.into_inner()),
+        Ok(_) => Err((Status::PayloadTooLarge, anyhow!("witness too large"))),
+        Err(err) => Err((Status::BadRequest, err.into())),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for Witness {
+    type Error = Error;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let bytes = match read_witness_bytes(req, data).await {
+            Ok(bytes) => bytes,
+            Err(err) => return data::Outcome::Error(err),
+        };
+        let inputs = if req.content_type() == Some(&ContentType::JSON) {
+            serde_json::from_slice::<Inputs>(&bytes).map_err(Error::from)
+        } else {
+            bincode::deserialize::<Inputs>(&bytes).map_err(Error::from)
+        };
+        match inputs {
+            Ok(inputs) => data::Outcome::Success(Witness(inputs)),
+            Err(err) => data::Outcome::Error((Status::BadRequest, err)),
+        }
+    }
+}
@@ -98,7 +98,120 @@ test_status() {
   assert_equal "$status" 'ok'
 }
 
+test_preflight() {
+  printf "test_preflight\n"
+
+  resp_head=$(mktemp)
+
+  curl \
+    -sS \
+    -D $resp_head \
+    -X OPTIONS \
+    -H 'origin: https://example.org' \
+    -H 'access-control-request-method: POST' \
+    http:/localhost:4190/proof \
+  > /dev/null
+
+  assert_status $resp_head 204
+  allow_origin="$(grep -i '^access-control-allow-origin:' $resp_head | tr -d '\r' | cut -d' ' -f2)"
+  assert_equal "$allow_origin" '*'
+}
+
+test_metrics() {
+  printf "test_metrics\n"
+
+  resp_head=$(mktemp)
+  resp_body=$(mktemp)
+
+  curl \
+    -sS \
+    -D $resp_head \
+    http:/localhost:4190/metrics \
+  > $resp_body
+
+  assert_status $resp_head 200
+  ok_count="$(grep '^sp1_safe_proof_requests_total{chain_id="100",outcome="ok"}' $resp_body | cut -d' ' -f2)"
+  assert_equal "$ok_count" '1'
+}
+
+test_job_failed() {
+  printf "test_job_failed\n"
+
+  resp_head=$(mktemp)
+  resp_body=$(mktemp)
+  not_ok_params="$(echo "$params" | sed 's/100/999999999999999/')"
+
+  curl \
+    -sS \
+    -D $resp_head \
+    http:/localhost:4190/jobs \
+    -d "$not_ok_params" \
+  > $resp_body
+
+  assert_status $resp_head 202
+  job_id="$(jq -r '.job_id' $resp_body)"
+
+  curl \
+    -sS \
+    -D $resp_head \
+    http:/localhost:4190/jobs/$job_id/events \
+  > $resp_body
+
+  assert_status $resp_head 200
+  last_event="$(grep '^data:' $resp_body | tail -n1 | cut -d: -f2- | jq -r '.event')"
+  assert_equal "$last_event" 'failed'
+}
+
+test_request_id() {
+  printf "test_request_id\n"
+
+  resp_head=$(mktemp)
+
+  curl \
+    -sS \
+    -D $resp_head \
+    http:/localhost:4190/status \
+  > /dev/null
+
+  assert_status $resp_head 200
+  generated="$(grep -i '^x-request-id:' $resp_head | tr -d '\r' | cut -d' ' -f2)"
+  assert_match "$generated" '^[a-f0-9-]{36}$'
+
+  curl \
+    -sS \
+    -D $resp_head \
+    -H 'x-request-id: abc-123' \
+    http:/localhost:4190/status \
+  > /dev/null
+
+  echoed="$(grep -i '^x-request-id:' $resp_head | tr -d '\r' | cut -d' ' -f2)"
+  assert_equal "$echoed" 'abc-123'
+}
+
+test_challenge() {
+  printf "test_challenge\n"
+
+  resp_head=$(mktemp)
+  resp_body=$(mktemp)
+
+  curl \
+    -sS \
+    -D $resp_head \
+    http:/localhost:4190/challenge \
+    -d "$params" \
+  > $resp_body
+
+  assert_status $resp_head 200
+  challenge="$(jq -r '.challenge' $resp_body)"
+  assert_equal "$challenge" '0x110bf0d0ea1251f73ef2ce30e6aec8fb2423e7c5d6563104789100168e252811'
+}
+
 test_proving_ok
 test_proving_not_ok
 test_wrong_chain_id
-test_status
\ No newline at end of file
+test_status
+test_preflight
+test_metrics
+test_job_failed
+test_request_id
+test_challenge
\ No newline at end of file
@@ -1,15 +1,69 @@
-use const_hex;
-use sp1_sdk::{HashableKey, ProverClient};
+//! Exports the Solidity PLONK verifier and an `SP1SafeConsumer.sol` embedding
+//! the program's vkey hash. Given a witness, as written by `sp1-safe fetch`,
+//! it also proves it to `fixtures/plonk.json` for the Foundry tests:
+//!
+//! ```sh
+//! cargo run --release -- ../witness.json
+//! ```
+
+use anyhow::{ensure, Context, Result};
+use serde_json::json;
+use sp1_safe_basics::{compute_challenge, witness::Witness, Inputs, PublicValues};
+use sp1_safe_prove::{Prover, ProverMode};
+use sp1_sdk::{HashableKey, PlonkBn254Proof, SP1Stdin};
 use std::path::PathBuf;
 
 const ELF: &[u8] = include_bytes!("../../program/elf/riscv32im-succinct-zkvm-elf");
+const CONSUMER: &str = include_str!("../templates/SP1SafeConsumer.sol.tmpl");
+
+fn hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", const_hex::encode(bytes))
+}
+
+fn main() -> Result<()> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    sp1_sdk::artifacts::export_solidity_plonk_bn254_verifier(dir.clone())
+        .context("failed to export verifier")?;
 
-fn main() {
-    // let client = ProverClient::new();
-    // let (_pk, vk) = client.setup(ELF);
+    let prover = Prover::new(ELF, ProverMode::Plonk);
+    let vkey = hex(prover.vk.hash_bytes());
+    std::fs::write(
+        dir.join("SP1SafeConsumer.sol"),
+        CONSUMER.replace("{{PROGRAM_VKEY}}", &vkey),
+    )?;
+    println!("vkey hash {}", vkey);
 
-    sp1_sdk::artifacts::export_solidity_plonk_bn254_verifier(PathBuf::from(env!("CARGO_MANIFEST_DIR")))
-        .expect("failed to export verifier");
+    let Some(path) = std::env::args().nth(1) else {
+        return Ok(());
+    };
+    let witness = Witness::from_bytes(&std::fs::read(&path)?)?;
+    let mut stdin = SP1Stdin::new();
+    stdin.write::<Inputs>(&witness.inputs);
+    let proven = prover.prove(stdin, |stage| eprintln!("{:?}", stage))?;
+    let public_values = PublicValues::decode(proven.public_values.as_slice())?;
+    let proof = bincode::deserialize::<PlonkBn254Proof>(&proven.proof)?;
+    ensure!(
+        public_values.challenge
+            == compute_challenge(witness.inputs.safe_address, witness.inputs.msg_hash),
+        "challenge mismatch"
+    );
 
-    // println!("✞✞✞✞✞ vk {}", const_hex::encode(&vk.hash_bytes()));
+    let fixture = json!({
+        "vkey": vkey,
+        "chainId": witness.chain_id,
+        "safeAddress": hex(witness.inputs.safe_address),
+        "messageHash": hex(witness.inputs.msg_hash),
+        "blockNumber": witness.block_number,
+        "blockHash": hex(public_values.block_hash),
+        "challenge": hex(public_values.challenge),
+        "publicValues": hex(public_values.encode()),
+        "proof": format!("0x{}", proof.encoded_proof),
+    });
+    std::fs::create_dir_all(dir.join("fixtures"))?;
+    std::fs::write(
+        dir.join("fixtures/plonk.json"),
+        serde_json::to_string_pretty(&fixture)? + "\n",
+    )?;
+    println!("wrote fixtures/plonk.json");
+    Ok(())
 }
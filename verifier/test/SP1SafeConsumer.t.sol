@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.19;
+
+import {Test} from "forge-std/Test.sol";
+import {SP1Verifier} from "../SP1Verifier.sol";
+import {SP1SafeConsumer} from "../SP1SafeConsumer.sol";
+
+contract SP1SafeConsumerTest is Test {
+    SP1SafeConsumer consumer;
+    address safe;
+    bytes32 messageHash;
+    uint256 blockNumber;
+    bytes32 blockHash;
+    bytes32 challenge;
+    bytes publicValues;
+    bytes proof;
+
+    function setUp() public {
+        consumer = new SP1SafeConsumer(new SP1Verifier());
+        string memory fixture = vm.readFile(string.concat(vm.projectRoot(), "/fixtures/plonk.json"));
+        assertEq(vm.parseJsonBytes32(fixture, ".vkey"), consumer.PROGRAM_VKEY());
+        safe = vm.parseJsonAddress(fixture, ".safeAddress");
+        messageHash = vm.parseJsonBytes32(fixture, ".messageHash");
+        blockNumber = vm.parseJsonUint(fixture, ".blockNumber");
+        blockHash = vm.parseJsonBytes32(fixture, ".blockHash");
+        challenge = vm.parseJsonBytes32(fixture, ".challenge");
+        publicValues = vm.parseJsonBytes(fixture, ".publicValues");
+        proof = vm.parseJsonBytes(fixture, ".proof");
+
+        vm.roll(blockNumber + 1);
+        vm.setBlockhash(blockNumber, blockHash);
+        consumer.registerChallenge(safe, messageHash, challenge);
+    }
+
+    function test_DecodePublicValues() public view {
+        (uint8 version, bytes32 _blockHash, bytes32 _challenge) = consumer.decodePublicValues(publicValues);
+        assertEq(version, consumer.PUBLIC_VALUES_VERSION());
+        assertEq(_blockHash, blockHash);
+        assertEq(_challenge, challenge);
+    }
+
+    function test_Consume() public {
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+        assertTrue(consumer.nullifiers(challenge));
+    }
+
+    function test_ConsumeUnboundToCaller() public {
+        vm.prank(address(0xbad));
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+        vm.expectRevert(abi.encodeWithSelector(SP1SafeConsumer.Nullified.selector, challenge));
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+    }
+
+    function test_RevertWhen_ConsumedTwice() public {
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+        vm.expectRevert(abi.encodeWithSelector(SP1SafeConsumer.Nullified.selector, challenge));
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+    }
+
+    function test_RevertWhen_BlockHashMismatch() public {
+        vm.setBlockhash(blockNumber, bytes32(0));
+        vm.expectRevert(abi.encodeWithSelector(SP1SafeConsumer.BlockHashMismatch.selector, blockNumber, blockHash));
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+    }
+
+    function test_RevertWhen_ChallengeMismatch() public {
+        // the proof's challenge relabeled as another Safe's
+        address other = address(0xdead);
+        consumer.registerChallenge(other, messageHash, bytes32(uint256(challenge) ^ 1));
+        vm.expectRevert(abi.encodeWithSelector(SP1SafeConsumer.ChallengeMismatch.selector, challenge));
+        consumer.consume(other, messageHash, blockNumber, publicValues, proof);
+    }
+
+    function test_RevertWhen_ChallengeUnknown() public {
+        bytes32 otherMessageHash = bytes32(uint256(messageHash) ^ 1);
+        vm.expectRevert(abi.encodeWithSelector(SP1SafeConsumer.UnknownChallenge.selector, safe, otherMessageHash));
+        consumer.consume(safe, otherMessageHash, blockNumber, publicValues, proof);
+    }
+
+    function test_RevertWhen_RegistrarUnauthorized() public {
+        vm.prank(address(0xbad));
+        vm.expectRevert(abi.encodeWithSelector(SP1SafeConsumer.Unauthorized.selector, address(0xbad)));
+        consumer.registerChallenge(safe, messageHash, challenge);
+    }
+
+    function test_RevertWhen_ProofInvalid() public {
+        proof[proof.length - 1] ^= 0x01;
+        vm.expectRevert();
+        consumer.consume(safe, messageHash, blockNumber, publicValues, proof);
+    }
+}
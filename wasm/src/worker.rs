@@ -0,0 +1,105 @@
+//! Message-based API for proving inside a Web Worker, keeping the main thread
+//! responsive:
+//!
+//! ```js
+//! // worker.js
+//! import init, { handleMessage } from "sp1-safe-script-wasm"
+//! await init()
+//! self.onmessage = ({ data }) => handleMessage(data, (msg, transfer) => self.postMessage(msg, transfer))
+//! ```
+//!
+//! Requests are `{ id, type: "build_witness", safe_address, message_hash,
+//! proof, block }` and `{ id, type: "prove", witness, mode }`, answered with
+//! `{ id, type: "witness", witness }` and `{ id, type: "proof", proof }`, the
+//! latter preceded by `{ id, type: "progress", progress }` events, or
+//! `{ id, type: "error", error }`. All binary fields are `Uint8Array`s whose
+//! buffers get transferred, not copied. Terminate the worker to cancel.
+
+use crate::{prove_with, prover_mode, witness, Proof, Witness};
+use serde::{Deserialize, Serialize};
+use sp1_safe_basics::Progress;
+use wasm_bindgen::prelude::*;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    BuildWitness {
+        safe_address: String,
+        message_hash: String,
+        /// `eth_getProof` result JSON
+        proof: String,
+        /// `eth_getBlockByNumber` result JSON
+        block: String,
+    },
+    Prove {
+        /// bincode-encoded inputs, a witness' `bincode`
+        #[serde(with = "serde_bytes")]
+        witness: Vec<u8>,
+        mode: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Response {
+    Progress { progress: Progress },
+    Witness { witness: Witness },
+    Proof { proof: Proof },
+    Error { error: String },
+}
+
+fn error_message(err: JsValue) -> String {
+    match err.dyn_ref::<js_sys::Error>() {
+        Some(err) => err.message().into(),
+        None => err.as_string().unwrap_or_else(|| format!("{:?}", err)),
+    }
+}
+
+/// Collects the buffers of all `Uint8Array`s in a response to transfer them.
+fn transferables(value: &JsValue, transfer: &js_sys::Array) {
+    if let Some(bytes) = value.dyn_ref::<js_sys::Uint8Array>() {
+        transfer.push(&bytes.buffer());
+    } else if value.is_object() && !js_sys::Array::is_array(value) {
+        for value in js_sys::Object::values(value.unchecked_ref()).iter() {
+            transferables(&value, transfer);
+        }
+    }
+}
+
+/// Handles a request message, calling `post(response, transfer)` for each
+/// response, fx with `self.postMessage`. Responses echo the request's `id`.
+#[wasm_bindgen(js_name = handleMessage)]
+pub async fn handle_message(message: JsValue, post: js_sys::Function) -> Result<(), JsValue> {
+    let id = js_sys::Reflect::get(&message, &"id".into())?;
+    let respond = |response: Response| -> Result<(), JsValue> {
+        let value = serde_wasm_bindgen::to_value(&response)?;
+        js_sys::Reflect::set(&value, &"id".into(), &id)?;
+        let transfer = js_sys::Array::new();
+        transferables(&value, &transfer);
+        post.call2(&JsValue::NULL, &value, &transfer)?;
+        Ok(())
+    };
+
+    let res = match serde_wasm_bindgen::from_value::<Request>(message) {
+        Ok(Request::BuildWitness {
+            safe_address,
+            message_hash,
+            proof,
+            block,
+        }) => witness(&safe_address, &message_hash, &proof, &block)
+            .map(|witness| Response::Witness { witness })
+            .map_err(JsValue::from),
+        Ok(Request::Prove { witness, mode }) => match prover_mode(mode) {
+            Ok(mode) => prove_with(&witness, mode, None, |progress| {
+                respond(Response::Progress { progress })
+            })
+            .await
+            .map(|proof| Response::Proof { proof }),
+            Err(err) => Err(err.into()),
+        },
+        Err(err) => Err(err.into()),
+    };
+    respond(res.unwrap_or_else(|err| Response::Error {
+        error: error_message(err),
+    }))
+}
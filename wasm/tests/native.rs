@@ -0,0 +1,46 @@
+//! Checks natively that a proof produced by the wasm build, committed as
+//! `fixtures/wasm-compressed.json` by `node.rs`' `write_proof_fixture`,
+//! verifies with the server's verifying key.
+#![cfg(not(target_arch = "wasm32"))]
+
+use sp1_safe_basics::{fixtures, keccak256, PublicValues, Sp1SafeResult};
+use sp1_safe_prove::{Prover, ProverMode};
+use sp1_safe_script_wasm::verify_result;
+use sp1_sdk::HashableKey;
+
+const SERVER_ELF: &[u8] = include_bytes!("../../program/elf/riscv32im-succinct-zkvm-elf");
+
+const WASM_PROOF: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/wasm-compressed.json"
+);
+
+#[test]
+#[ignore = "needs tests/fixtures/wasm-compressed.json from a wasm32 build, see README"]
+fn test_server_verifies_wasm_proof() {
+    let result = serde_json::from_str::<Sp1SafeResult>(
+        &std::fs::read_to_string(WASM_PROOF).expect("missing wasm proof fixture"),
+    )
+    .unwrap();
+    let public_values = PublicValues::from_hex(&result.block_hash, &result.challenge).unwrap();
+    assert_eq!(
+        public_values.block_hash,
+        keccak256(&fixtures::inputs().header_rlp)
+    );
+
+    let server = Prover::new(SERVER_ELF, ProverMode::Compressed);
+    server
+        .verify(
+            &const_hex::decode(&result.proof).unwrap(),
+            &public_values.encode(),
+        )
+        .unwrap();
+
+    let hex = |bytes: &[u8]| format!("0x{}", const_hex::encode(bytes));
+    let vkey_hash = hex(&server.vk.hash_bytes());
+    assert_eq!(
+        verify_result(&result, &vkey_hash, ProverMode::Compressed).unwrap(),
+        public_values
+    );
+    assert!(verify_result(&result, &hex(&[0; 32]), ProverMode::Compressed).is_err());
+}
@@ -0,0 +1,111 @@
+//! Worker API tests, run under Node via `wasm-pack test --node`.
+#![cfg(target_arch = "wasm32")]
+
+use sp1_safe_basics::{fixtures, Sp1SafeResult};
+use sp1_safe_prove::ProverMode;
+use sp1_safe_script_wasm::{prove_inputs, worker::handle_message};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen(module = "fs")]
+extern "C" {
+    #[wasm_bindgen(js_name = writeFileSync)]
+    fn write_file_sync(path: &str, data: &str);
+}
+
+/// Sends a message, returning all `[response, transfer]` pairs posted.
+async fn request(message: &str) -> Vec<(JsValue, js_sys::Array)> {
+    let posted = js_sys::Array::new();
+    let post = Closure::<dyn Fn(JsValue, js_sys::Array)>::new({
+        let posted = posted.clone();
+        move |response, transfer: js_sys::Array| {
+            posted.push(&js_sys::Array::of2(&response, &transfer));
+        }
+    });
+    let message = js_sys::JSON::parse(message).unwrap();
+    handle_message(
+        message,
+        post.as_ref().unchecked_ref::<js_sys::Function>().clone(),
+    )
+    .await
+    .unwrap();
+    posted
+        .iter()
+        .map(|pair| {
+            let pair = pair.unchecked_into::<js_sys::Array>();
+            (pair.get(0), pair.get(1).unchecked_into())
+        })
+        .collect()
+}
+
+fn get(value: &JsValue, key: &str) -> JsValue {
+    js_sys::Reflect::get(value, &key.into()).unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn test_build_witness() {
+    let message = serde_json::json!({
+        "id": 7,
+        "type": "build_witness",
+        "safe_address": fixtures::SAFE_ADDRESS,
+        "message_hash": fixtures::MESSAGE_HASH,
+        "proof": include_str!("../../lib/fetch/fixtures/gnosis-33073608-proof.json"),
+        "block": include_str!("../../lib/fetch/fixtures/gnosis-33073608-block.json"),
+    });
+    let responses = request(&message.to_string()).await;
+    assert_eq!(responses.len(), 1);
+    let (response, transfer) = &responses[0];
+    assert_eq!(get(response, "id").as_f64(), Some(7.0));
+    assert_eq!(get(response, "type").as_string().unwrap(), "witness");
+    let witness = get(response, "witness");
+    assert_eq!(
+        get(&witness, "challenge").as_string().unwrap(),
+        "0x110bf0d0ea1251f73ef2ce30e6aec8fb2423e7c5d6563104789100168e252811"
+    );
+    let bincode = get(&witness, "bincode").unchecked_into::<js_sys::Uint8Array>();
+    assert!(bincode.length() > 0);
+    assert_eq!(transfer.length(), 1);
+    assert_eq!(transfer.get(0), JsValue::from(bincode.buffer()));
+}
+
+#[wasm_bindgen_test]
+async fn test_errors() {
+    let responses = request(r#"{ "id": "a", "type": "prove", "witness": [1, 2, 3] }"#).await;
+    assert_eq!(responses.len(), 1);
+    let (response, transfer) = &responses[0];
+    assert_eq!(get(response, "id").as_string().unwrap(), "a");
+    assert_eq!(get(response, "type").as_string().unwrap(), "error");
+    assert!(get(response, "error").as_string().is_some());
+    assert_eq!(transfer.length(), 0);
+
+    let responses =
+        request(r#"{ "id": 1, "type": "prove", "witness": [], "mode": "plonkish" }"#).await;
+    assert_eq!(get(&responses[0].0, "type").as_string().unwrap(), "error");
+
+    let responses = request(r#"{ "id": 2, "type": "unknown" }"#).await;
+    assert_eq!(get(&responses[0].0, "type").as_string().unwrap(), "error");
+}
+
+/// Proves the shared fixture with the wasm build, writing the result that
+/// `native.rs` verifies with the server's verifying key. Run it explicitly via
+/// `wasm-pack test --release --node -- --test node -- --include-ignored`.
+#[wasm_bindgen_test]
+#[ignore]
+fn write_proof_fixture() {
+    let inputs = bincode::serialize(&fixtures::inputs()).unwrap();
+    let (public_values, proof) = prove_inputs(ProverMode::Compressed, &inputs, |_| Ok(())).unwrap();
+    let hex = |bytes: &[u8]| format!("0x{}", const_hex::encode(bytes));
+    let result = Sp1SafeResult {
+        block_hash: hex(&public_values.block_hash),
+        challenge: hex(&public_values.challenge),
+        proof: hex(&proof),
+        ..fixtures::result()
+    };
+    write_file_sync(
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/wasm-compressed.json"
+        ),
+        &serde_json::to_string_pretty(&result).unwrap(),
+    );
+}